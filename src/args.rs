@@ -3,6 +3,7 @@ use crate::filters::{Filter, Filters};
 use crate::flags::{Flag, Flags};
 use crate::help::HELP_TEXT;
 use crate::io::{InputOutput, IO};
+use crate::settings::{Setting, Settings};
 
 /// The `Args` struct represents the parsed command-line arguments for the application.
 ///
@@ -11,6 +12,7 @@ use crate::io::{InputOutput, IO};
 /// * `help`: An `Option<String>` containing the help information, if the `--help` flag was used.
 /// * `flags`: A `Flags` struct containing the parsed command-line flags and their values.
 /// * `filters`: A `Filters` struct containing the filters to be applied to the environment variables.
+/// * `settings`: A `Settings` struct containing the options that take a value.
 #[derive(Debug, Default)]
 pub struct Args {
     pub io: InputOutput,
@@ -18,6 +20,7 @@ pub struct Args {
     pub help: Option<String>,
     pub flags: Flags,
     pub filters: Filters,
+    pub settings: Settings,
 }
 
 impl Args {
@@ -28,6 +31,7 @@ impl Args {
     /// * `help`: Set to `None` by default, as the `--help` flag is not set.
     /// * `flags`: A `Flags` object with default values.
     /// * `filters`: A `Filters` object with default values.
+    /// * `settings`: A `Settings` object with default values.
     ///
     /// Returns a new `Args` instance with the default configuration.
     fn new() -> Self {
@@ -37,6 +41,7 @@ impl Args {
             help: None,
             flags: Flags::default(),
             filters: Filters::default(),
+            settings: Settings::default(),
         }
     }
 
@@ -206,6 +211,27 @@ impl Args {
                     "-c" | "--color" => {
                         parsed_args.flags.set(Flag::Color, flag_name, true)?;
                     }
+                    "--directives" => {
+                        parsed_args.flags.set(Flag::Directives, flag_name, true)?;
+                    }
+                    "--relaxed-names" => {
+                        parsed_args.flags.set(Flag::RelaxedNames, flag_name, true)?;
+                    }
+                    "--keep-trailing-newline" => {
+                        parsed_args
+                            .flags
+                            .set(Flag::KeepTrailingNewline, flag_name, true)?;
+                    }
+                    "--prefer-env" => {
+                        parsed_args
+                            .flags
+                            .set(Flag::PreferEnvironment, flag_name, true)?;
+                    }
+                    "--multiline-expressions" => {
+                        parsed_args
+                            .flags
+                            .set(Flag::MultilineExpressions, flag_name, true)?;
+                    }
 
                     // FILTERS
                     "-p" | "--prefix" => {
@@ -223,6 +249,46 @@ impl Args {
                             .filters
                             .add(Filter::Variable, flag_name, value, &mut args)?;
                     }
+                    // SETTINGS
+                    "--max-expression-length" => {
+                        parsed_args.settings.set(
+                            Setting::MaxExpressionLength,
+                            flag_name,
+                            value,
+                            &mut args,
+                        )?;
+                    }
+                    "--directive-prefix" => {
+                        parsed_args.settings.set(
+                            Setting::DirectivePrefix,
+                            flag_name,
+                            value,
+                            &mut args,
+                        )?;
+                    }
+                    "--include-root" => {
+                        parsed_args.settings.set(
+                            Setting::IncludeRoot,
+                            flag_name,
+                            value,
+                            &mut args,
+                        )?;
+                    }
+                    "--dialect" => {
+                        parsed_args
+                            .settings
+                            .set(Setting::Dialect, flag_name, value, &mut args)?;
+                    }
+                    "--env-file" => {
+                        parsed_args
+                            .settings
+                            .set(Setting::EnvFile, flag_name, value, &mut args)?;
+                    }
+                    "--env-dir" => {
+                        parsed_args
+                            .settings
+                            .set(Setting::EnvDir, flag_name, value, &mut args)?;
+                    }
                     // UNKNOWN
                     _ => return Err(ParseArgsError::UnknownFlag(flag)),
                 }
@@ -236,6 +302,7 @@ impl Args {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::settings::{Dialect, VariableSource};
 
     #[test]
     fn test_parse() {
@@ -495,6 +562,97 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_max_expression_length() {
+        let args = vec!["--multiline-expressions", "--max-expression-length=100"];
+        let parsed_args = Args::parse(args);
+        assert!(parsed_args.is_ok());
+        let parsed_args = parsed_args.unwrap();
+        assert!(parsed_args.flags.is_flag_set(Flag::MultilineExpressions));
+        assert_eq!(parsed_args.settings.max_expression_length(), 100);
+
+        let args = vec!["--max-expression-length", "lots"];
+        let parsed_args = Args::parse(args);
+        assert_eq!(
+            parsed_args.unwrap_err(),
+            ParseArgsError::InvalidValue("--max-expression-length".to_string(), "lots".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_dialect() {
+        let args = vec!["--dialect", "mustache"];
+        let parsed_args = Args::parse(args).unwrap();
+        assert_eq!(parsed_args.settings.dialect(), Dialect::Mustache);
+
+        let args = vec!["--dialect=django"];
+        let parsed_args = Args::parse(args);
+        assert_eq!(
+            parsed_args.unwrap_err(),
+            ParseArgsError::InvalidValue("--dialect".to_string(), "django".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_directives() {
+        let args = vec!["--directives", "--directive-prefix", "@@"];
+        let parsed_args = Args::parse(args).unwrap();
+        assert!(parsed_args.flags.is_flag_set(Flag::Directives));
+        assert_eq!(parsed_args.settings.directive_prefix(), "@@");
+
+        let args = vec!["--include-root", "templates"];
+        let parsed_args = Args::parse(args).unwrap();
+        assert_eq!(
+            parsed_args.settings.include_root(),
+            std::path::PathBuf::from("templates")
+        );
+
+        let args = vec!["--directive-prefix=", "--directives"];
+        let parsed_args = Args::parse(args);
+        assert_eq!(
+            parsed_args.unwrap_err(),
+            ParseArgsError::InvalidValue("--directive-prefix".to_string(), String::new())
+        );
+    }
+
+    #[test]
+    fn test_parse_relaxed_names() {
+        let args = vec!["--relaxed-names"];
+        let parsed_args = Args::parse(args).unwrap();
+        assert!(parsed_args.flags.is_flag_set(Flag::RelaxedNames));
+        assert!(!parsed_args.flags.is_flag_set(Flag::Directives));
+    }
+
+    #[test]
+    fn test_parse_variable_sources() {
+        let args = vec![
+            "--env-file",
+            ".env",
+            "--env-dir=/run/secrets",
+            "--env-file=.env.local",
+            "--prefer-env",
+            "--keep-trailing-newline",
+        ];
+        let parsed_args = Args::parse(args).unwrap();
+        assert_eq!(
+            parsed_args.settings.variable_sources(),
+            [
+                VariableSource::EnvFile(std::path::PathBuf::from(".env")),
+                VariableSource::EnvDir(std::path::PathBuf::from("/run/secrets")),
+                VariableSource::EnvFile(std::path::PathBuf::from(".env.local")),
+            ]
+        );
+        assert!(parsed_args.flags.is_flag_set(Flag::KeepTrailingNewline));
+        assert!(parsed_args.flags.is_flag_set(Flag::PreferEnvironment));
+
+        let args = vec!["--env-file", "--prefer-env"];
+        let parsed_args = Args::parse(args);
+        assert_eq!(
+            parsed_args.unwrap_err(),
+            ParseArgsError::MissingValue("--env-file".to_string())
+        );
+    }
+
     #[test]
     fn test_parse_prefix_equal() {
         let args = vec!["--prefix=prefix-"];
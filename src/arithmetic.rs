@@ -0,0 +1,457 @@
+/// A token of an arithmetic expression.
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Operator(&'static str),
+    LeftParen,
+    RightParen,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(number) => return write!(f, "{number}"),
+            Self::Name(name) => return write!(f, "{name}"),
+            Self::Operator(operator) => return write!(f, "{operator}"),
+            Self::LeftParen => return write!(f, "("),
+            Self::RightParen => return write!(f, ")"),
+        }
+    }
+}
+
+/// A parsed arithmetic expression.
+#[derive(Debug)]
+enum Expr {
+    Number(i64),
+    Variable(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+/// The operators, longest first so that e.g. `<=` is not read as `<`.
+const OPERATORS: &[&str] = &[
+    "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "&",
+    "|", "^", "!", "~", "?", ":",
+];
+
+/// The binary operators from the lowest to the highest precedence. `?:` has a lower and the
+/// unary operators have a higher precedence than all of them.
+const BINARY_OPERATORS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+    &["**"],
+];
+
+/// The maximum number of nested parentheses, unary operators, conditional operators and `**`
+/// operators. The parser and the evaluation are recursive, so deeper expressions fail with an
+/// error instead of overflowing the stack.
+const MAX_NESTING_DEPTH: usize = 100;
+
+/// The maximum number of tokens of an expression, which limits the depth of long chains of binary
+/// operators like `1 + 1 + ... + 1`.
+const MAX_TOKENS: usize = 1000;
+
+/// Parses an integer with an optional sign, either decimal or hexadecimal with a `0x` prefix.
+///
+/// # Arguments
+///
+/// * `number`: The number to parse, e.g. `42`, `-1` or `0xff`.
+///
+/// # Returns
+///
+/// The value, or `None` if `number` is not a valid integer.
+///
+pub fn parse_number(number: &str) -> Option<i64> {
+    let (negative, digits) = match number.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, number.strip_prefix('+').unwrap_or(number)),
+    };
+
+    let value = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) if !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+            i64::from_str_radix(hex, 16).ok()?
+        }
+        None if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) => {
+            digits.parse::<i64>().ok()?
+        }
+        _ => return None,
+    };
+
+    if negative {
+        return value.checked_neg();
+    }
+    return Some(value);
+}
+
+/// Splits an arithmetic expression into tokens.
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c.is_ascii_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if c.is_ascii_digit() {
+                let number =
+                    parse_number(&word).ok_or_else(|| format!("Invalid number: '{word}'"))?;
+                tokens.push(Token::Number(number));
+            } else {
+                tokens.push(Token::Name(word));
+            }
+            continue;
+        }
+
+        match c {
+            '(' => tokens.push(Token::LeftParen),
+            ')' => tokens.push(Token::RightParen),
+            _ => {
+                let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                let operator = OPERATORS
+                    .iter()
+                    .find(|operator| rest.starts_with(**operator))
+                    .ok_or_else(|| format!("Invalid character: '{c}'"))?;
+                tokens.push(Token::Operator(operator));
+                i += operator.len();
+                continue;
+            }
+        }
+        i += 1;
+    }
+
+    return Ok(tokens);
+}
+
+/// A recursive descent parser for arithmetic expressions.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// The current nesting depth, see `MAX_NESTING_DEPTH`.
+    depth: usize,
+}
+
+impl Parser {
+    /// Parses a nested expression with `parse`, failing if the maximum depth is exceeded.
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Expr, String>,
+    ) -> Result<Expr, String> {
+        if self.depth == MAX_NESTING_DEPTH {
+            return Err(format!(
+                "Expression is nested more than {MAX_NESTING_DEPTH} levels deep"
+            ));
+        }
+        self.depth += 1;
+        let expr = parse(self)?;
+        self.depth -= 1;
+        return Ok(expr);
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        return self.tokens.get(self.position);
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        return token;
+    }
+
+    /// Consumes the operator `operator` if it is the next token.
+    fn accept(&mut self, operator: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Operator(o)) if *o == operator) {
+            self.position += 1;
+            return true;
+        }
+        return false;
+    }
+
+    /// Parses a conditional expression `condition ? then : else`.
+    fn conditional(&mut self) -> Result<Expr, String> {
+        let condition = self.binary(0)?;
+        if !self.accept("?") {
+            return Ok(condition);
+        }
+        let then = self.nested(Self::conditional)?;
+        if !self.accept(":") {
+            return Err("Missing ':' in conditional expression".to_string());
+        }
+        let otherwise = self.nested(Self::conditional)?;
+        return Ok(Expr::Conditional(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ));
+    }
+
+    /// Parses binary operators with the precedence `level` or higher.
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == BINARY_OPERATORS.len() {
+            return self.unary();
+        }
+
+        let mut left = self.binary(level + 1)?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Operator(operator)) if BINARY_OPERATORS[level].contains(operator) => {
+                    *operator
+                }
+                _ => return Ok(left),
+            };
+            self.position += 1;
+
+            // '**' is right-associative
+            let right = if operator == "**" {
+                self.nested(|parser| return parser.binary(level))?
+            } else {
+                self.binary(level + 1)?
+            };
+            left = Expr::Binary(operator, Box::new(left), Box::new(right));
+        }
+    }
+
+    /// Parses unary operators, numbers, variables and parentheses.
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Operator(operator @ ("-" | "+" | "!" | "~"))) => {
+                return Ok(Expr::Unary(operator, Box::new(self.nested(Self::unary)?)));
+            }
+            Some(Token::Number(number)) => return Ok(Expr::Number(number)),
+            Some(Token::Name(name)) => return Ok(Expr::Variable(name)),
+            Some(Token::LeftParen) => {
+                let expr = self.nested(Self::conditional)?;
+                if self.next() != Some(Token::RightParen) {
+                    return Err("Missing ')'".to_string());
+                }
+                return Ok(expr);
+            }
+            Some(token) => return Err(format!("Unexpected '{token}'")),
+            None => return Err("Unexpected end of expression".to_string()),
+        }
+    }
+}
+
+/// Evaluates a parsed expression. `&&`, `||` and `?:` only evaluate the operands they need.
+fn eval(expr: &Expr, lookup: &mut dyn FnMut(&str) -> Result<i64, String>) -> Result<i64, String> {
+    let overflow = || "Arithmetic overflow".to_string();
+
+    match expr {
+        Expr::Number(number) => return Ok(*number),
+        Expr::Variable(name) => return lookup(name),
+        Expr::Unary(operator, operand) => {
+            let value = eval(operand, lookup)?;
+            match *operator {
+                "-" => return value.checked_neg().ok_or_else(overflow),
+                "!" => return Ok(i64::from(value == 0)),
+                "~" => return Ok(!value),
+                _ => return Ok(value),
+            }
+        }
+        Expr::Conditional(condition, then, otherwise) => {
+            if eval(condition, lookup)? != 0 {
+                return eval(then, lookup);
+            }
+            return eval(otherwise, lookup);
+        }
+        Expr::Binary("&&", left, right) => {
+            let result = eval(left, lookup)? != 0 && eval(right, lookup)? != 0;
+            return Ok(i64::from(result));
+        }
+        Expr::Binary("||", left, right) => {
+            let result = eval(left, lookup)? != 0 || eval(right, lookup)? != 0;
+            return Ok(i64::from(result));
+        }
+        Expr::Binary(operator, left, right) => {
+            let left = eval(left, lookup)?;
+            let right = eval(right, lookup)?;
+            let shift = || u32::try_from(right).map_err(|_| format!("Invalid shift: {right}"));
+
+            match *operator {
+                "+" => return left.checked_add(right).ok_or_else(overflow),
+                "-" => return left.checked_sub(right).ok_or_else(overflow),
+                "*" => return left.checked_mul(right).ok_or_else(overflow),
+                "/" | "%" if right == 0 => return Err("Division by zero".to_string()),
+                "/" => return left.checked_div(right).ok_or_else(overflow),
+                "%" => return left.checked_rem(right).ok_or_else(overflow),
+                "**" => {
+                    let exponent =
+                        u32::try_from(right).map_err(|_| format!("Invalid exponent: {right}"))?;
+                    return left.checked_pow(exponent).ok_or_else(overflow);
+                }
+                "<<" => return left.checked_shl(shift()?).ok_or_else(overflow),
+                ">>" => return left.checked_shr(shift()?).ok_or_else(overflow),
+                "<" => return Ok(i64::from(left < right)),
+                "<=" => return Ok(i64::from(left <= right)),
+                ">" => return Ok(i64::from(left > right)),
+                ">=" => return Ok(i64::from(left >= right)),
+                "==" => return Ok(i64::from(left == right)),
+                "!=" => return Ok(i64::from(left != right)),
+                "&" => return Ok(left & right),
+                "|" => return Ok(left | right),
+                _ => return Ok(left ^ right),
+            }
+        }
+    }
+}
+
+/// Evaluates an integer arithmetic expression, like the shell arithmetic expansion `$((...))`.
+///
+/// Supported are decimal and hexadecimal (`0x`) numbers, variables, parentheses, the unary
+/// operators `-`, `+`, `!` and `~`, the binary operators `**`, `*`, `/`, `%`, `+`, `-`, `<<`,
+/// `>>`, `<`, `<=`, `>`, `>=`, `==`, `!=`, `&`, `^`, `|`, `&&` and `||`, and the conditional
+/// operator `?:`, with the precedence of bash. All calculations use 64-bit signed integers.
+///
+/// # Arguments
+///
+/// * `expression`: The expression to evaluate, e.g. `PORT + 1`.
+/// * `lookup`: Returns the value of a variable, or an error if the variable cannot be used.
+///
+/// # Returns
+///
+/// * `Ok(i64)` - The result of the expression.
+/// * `Err(String)` - An error string if the expression is invalid, too long or nested too deep,
+///   a variable cannot be looked up, a division by zero or an overflow occurs.
+pub fn evaluate(
+    expression: &str,
+    lookup: &mut dyn FnMut(&str) -> Result<i64, String>,
+) -> Result<i64, String> {
+    let tokens = tokenize(expression)?;
+    if tokens.len() > MAX_TOKENS {
+        return Err(format!("Expression has more than {MAX_TOKENS} tokens"));
+    }
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        depth: 0,
+    };
+    let expr = parser.conditional()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("Unexpected '{token}'"));
+    }
+    return eval(&expr, lookup);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calculate(expression: &str) -> Result<i64, String> {
+        return evaluate(expression, &mut |name| match name {
+            "WORKERS" => Ok(4),
+            "PORT" => Ok(8080),
+            _ => Err(format!("Unknown variable: '{name}'")),
+        });
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("42"), Some(42));
+        assert_eq!(parse_number("-42"), Some(-42));
+        assert_eq!(parse_number("+0x1F"), Some(31));
+        assert_eq!(parse_number("0x"), None);
+        assert_eq!(parse_number("0x-1"), None);
+        assert_eq!(parse_number("4 2"), None);
+        assert_eq!(parse_number(""), None);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(calculate("WORKERS * 2 + 1"), Ok(9));
+        assert_eq!(calculate("PORT + 1"), Ok(8081));
+        assert_eq!(calculate("(1 + 2) * 3"), Ok(9));
+        assert_eq!(calculate("2 ** 3 ** 2"), Ok(512));
+        assert_eq!(calculate("-2 ** 2"), Ok(4));
+        assert_eq!(calculate("7 / 2 + 7 % 2 - -1"), Ok(5));
+        assert_eq!(calculate("1 << 4 | 0x0f"), Ok(31));
+        assert_eq!(calculate("~0 ^ 1"), Ok(-2));
+        assert_eq!(calculate("WORKERS > 2 && !0"), Ok(1));
+        assert_eq!(calculate("WORKERS <= 2 || 0"), Ok(0));
+        assert_eq!(calculate("WORKERS == 4 ? 10 : 20"), Ok(10));
+        assert_eq!(calculate("0 ? 1 : 0 ? 2 : 3"), Ok(3));
+    }
+
+    #[test]
+    fn test_evaluate_short_circuit() {
+        assert_eq!(calculate("0 && UNKNOWN"), Ok(0));
+        assert_eq!(calculate("1 || 1 / 0"), Ok(1));
+        assert_eq!(calculate("WORKERS ? 100 / WORKERS : 1 / 0"), Ok(25));
+    }
+
+    #[test]
+    fn test_evaluate_errors() {
+        assert_eq!(calculate("1 / 0"), Err("Division by zero".to_string()));
+        assert_eq!(
+            calculate("1 % (2 - 2)"),
+            Err("Division by zero".to_string())
+        );
+        assert_eq!(
+            calculate("9223372036854775807 + 1"),
+            Err("Arithmetic overflow".to_string())
+        );
+        assert_eq!(calculate("2 ** 64"), Err("Arithmetic overflow".to_string()));
+        assert_eq!(
+            calculate("2 ** -1"),
+            Err("Invalid exponent: -1".to_string())
+        );
+        assert_eq!(
+            calculate("UNKNOWN + 1"),
+            Err("Unknown variable: 'UNKNOWN'".to_string())
+        );
+        assert_eq!(
+            calculate("1 +"),
+            Err("Unexpected end of expression".to_string())
+        );
+        assert_eq!(calculate("(1 + 2"), Err("Missing ')'".to_string()));
+        assert_eq!(calculate("* 2"), Err("Unexpected '*'".to_string()));
+        assert_eq!(calculate("1 2"), Err("Unexpected '2'".to_string()));
+        assert_eq!(
+            calculate("1 = 2"),
+            Err("Invalid character: '='".to_string())
+        );
+        assert_eq!(calculate("08x"), Err("Invalid number: '08x'".to_string()));
+    }
+
+    #[test]
+    fn test_evaluate_limits() {
+        let nested = |depth| return format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        let nested_error = Err(format!(
+            "Expression is nested more than {MAX_NESTING_DEPTH} levels deep"
+        ));
+        assert_eq!(calculate(&nested(MAX_NESTING_DEPTH)), Ok(1));
+        assert_eq!(calculate(&nested(MAX_NESTING_DEPTH + 1)), nested_error);
+        assert_eq!(calculate(&nested(400)), nested_error);
+        assert_eq!(calculate(&format!("{}1", "-".repeat(900))), nested_error);
+        assert_eq!(calculate(&"1 ? ".repeat(400)), nested_error);
+        assert_eq!(calculate(&"2 ** ".repeat(400)), nested_error);
+
+        let chain = |terms| return vec!["1"; terms].join(" + ");
+        assert_eq!(calculate(&chain(MAX_TOKENS / 2)), Ok(500));
+        assert_eq!(
+            calculate(&chain(10_000)),
+            Err(format!("Expression has more than {MAX_TOKENS} tokens"))
+        );
+    }
+}
@@ -0,0 +1,1069 @@
+use crate::env_subst::{expand_argument, read_lines, replace_lines};
+use crate::filters::Filters;
+use crate::flags::Flags;
+use crate::settings::Settings;
+use crate::variable_expansion::is_valid_name;
+use crate::variables::Variables;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// The keywords of the block directives.
+const KEYWORDS: &[&str] = &["if", "else", "endif", "each", "end", "include"];
+
+/// A directive line, e.g. `#if VAR`.
+#[derive(Debug, PartialEq, Eq)]
+struct Directive<'a> {
+    /// The keyword following the directive prefix, e.g. `if`.
+    keyword: &'a str,
+    /// The trimmed rest of the line after the keyword.
+    argument: &'a str,
+    /// The line number, starting at 1.
+    line: usize,
+}
+
+/// A parsed part of a template.
+#[derive(Debug, PartialEq, Eq)]
+enum Block<'a> {
+    /// Lines without directives, including their line endings.
+    Text(Vec<&'a str>),
+    /// An `#if` block with the lines of both branches.
+    If {
+        condition: &'a str,
+        line: usize,
+        then: Vec<Block<'a>>,
+        otherwise: Vec<Block<'a>>,
+    },
+    /// An `#each` block, which is repeated for every item of the list.
+    Each {
+        name: &'a str,
+        list: &'a str,
+        separator: Option<&'a str>,
+        line: usize,
+        body: Vec<Block<'a>>,
+    },
+    /// An `#include` of another template file.
+    Include { path: &'a str, line: usize },
+}
+
+/// Formats an error of the directive on `line` of the included file `source`, or of the input if
+/// `source` is `None`.
+fn directive_error(source: Option<&str>, line: usize, message: &str) -> String {
+    return match source {
+        Some(source) => format!("failed to process directives: {source}: line {line}: {message}"),
+        None => format!("failed to process directives: line {line}: {message}"),
+    };
+}
+
+/// Parses `line` as a directive.
+///
+/// A directive is a line that starts with the `prefix`, optionally preceded by whitespace,
+/// directly followed by one of the `KEYWORDS`. Other lines, e.g. comments like `# if needed`,
+/// are regular text.
+///
+/// # Arguments
+///
+/// * `line` - The line to parse, including its line ending.
+/// * `number` - The line number, starting at 1.
+/// * `prefix` - The directive prefix, e.g. `#`.
+///
+/// # Returns
+///
+/// * `Option<Directive>` - The directive, or `None` if the line is not a directive.
+///
+fn parse_directive<'a>(line: &'a str, number: usize, prefix: &str) -> Option<Directive<'a>> {
+    let rest = line.trim().strip_prefix(prefix)?;
+    let (keyword, argument) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+    if !KEYWORDS.contains(&keyword) {
+        return None;
+    }
+
+    return Some(Directive {
+        keyword,
+        argument: argument.trim(),
+        line: number,
+    });
+}
+
+/// `Parser` splits the lines of a template into blocks.
+struct Parser<'a> {
+    lines: &'a [String],
+    prefix: &'a str,
+    /// The name of the included file, or `None` for the input.
+    source: Option<&'a str>,
+    /// The index of the next line to parse.
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Parses blocks until one of the `terminators` or the end of the lines is reached.
+    ///
+    /// # Arguments
+    ///
+    /// * `terminators` - The keywords that end the current block, e.g. `else` and `endif`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(Vec<Block>, Option<Directive>), String>` - The parsed blocks and the directive
+    ///   that ended them, or `None` if the end of the lines was reached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directive is not expected at its position, if it has an invalid
+    /// argument, or if an `#if` is not closed.
+    ///
+    fn parse_blocks(
+        &mut self,
+        terminators: &[&str],
+    ) -> Result<(Vec<Block<'a>>, Option<Directive<'a>>), String> {
+        let mut blocks = Vec::new();
+        let mut text = Vec::new();
+
+        while let Some(line) = self.lines.get(self.position) {
+            self.position += 1;
+
+            let Some(directive) = parse_directive(line, self.position, self.prefix) else {
+                text.push(line.as_str());
+                continue;
+            };
+
+            if !text.is_empty() {
+                blocks.push(Block::Text(std::mem::take(&mut text)));
+            }
+
+            if terminators.contains(&directive.keyword) {
+                return Ok((blocks, Some(directive)));
+            }
+
+            let prefix = self.prefix;
+            match directive.keyword {
+                "if" => {
+                    if directive.argument.is_empty() {
+                        return Err(directive_error(
+                            self.source,
+                            directive.line,
+                            &format!("'{prefix}if' requires a condition"),
+                        ));
+                    }
+                    blocks.push(self.parse_if(&directive)?);
+                }
+                "each" => {
+                    blocks.push(self.parse_each(&directive)?);
+                }
+                "include" => {
+                    if directive.argument.is_empty() {
+                        return Err(directive_error(
+                            self.source,
+                            directive.line,
+                            &format!("'{prefix}include' requires a path"),
+                        ));
+                    }
+                    blocks.push(Block::Include {
+                        path: directive.argument,
+                        line: directive.line,
+                    });
+                }
+                keyword => {
+                    return Err(directive_error(
+                        self.source,
+                        directive.line,
+                        &format!("unexpected '{prefix}{keyword}'"),
+                    ));
+                }
+            }
+        }
+
+        if !text.is_empty() {
+            blocks.push(Block::Text(text));
+        }
+
+        return Ok((blocks, None));
+    }
+
+    /// Parses the branches of the `#if` block started by `directive`.
+    fn parse_if(&mut self, directive: &Directive<'a>) -> Result<Block<'a>, String> {
+        let (prefix, source) = (self.prefix, self.source);
+        let not_closed = || {
+            return directive_error(
+                source,
+                directive.line,
+                &format!("'{prefix}if' is not closed with '{prefix}endif'"),
+            );
+        };
+
+        let (then, end) = self.parse_blocks(&["else", "endif"])?;
+        let end = end.ok_or_else(not_closed)?;
+        self.check_no_argument(&end)?;
+
+        let mut otherwise = Vec::new();
+        if end.keyword == "else" {
+            let (blocks, end) = self.parse_blocks(&["endif"])?;
+            let end = end.ok_or_else(not_closed)?;
+            self.check_no_argument(&end)?;
+            otherwise = blocks;
+        }
+
+        return Ok(Block::If {
+            condition: directive.argument,
+            line: directive.line,
+            then,
+            otherwise,
+        });
+    }
+
+    /// Parses the body of the `#each` block started by `directive`.
+    ///
+    /// The argument has the form `NAME in LIST`, optionally followed by `by "SEPARATOR"`.
+    fn parse_each(&mut self, directive: &Directive<'a>) -> Result<Block<'a>, String> {
+        let prefix = self.prefix;
+        let (name, list, separator) = parse_loop(directive.argument).ok_or_else(|| {
+            return directive_error(
+                self.source,
+                directive.line,
+                &format!("'{prefix}each' requires 'NAME in LIST'"),
+            );
+        })?;
+
+        if !is_valid_name(name) {
+            return Err(directive_error(
+                self.source,
+                directive.line,
+                &format!("invalid loop variable '{name}'"),
+            ));
+        }
+        if separator == Some("") {
+            return Err(directive_error(
+                self.source,
+                directive.line,
+                "the separator must not be empty",
+            ));
+        }
+
+        let (body, end) = self.parse_blocks(&["end"])?;
+        let end = end.ok_or_else(|| {
+            return directive_error(
+                self.source,
+                directive.line,
+                &format!("'{prefix}each' is not closed with '{prefix}end'"),
+            );
+        })?;
+        self.check_no_argument(&end)?;
+
+        return Ok(Block::Each {
+            name,
+            list,
+            separator,
+            line: directive.line,
+            body,
+        });
+    }
+
+    /// Returns an error if `directive` has an argument.
+    fn check_no_argument(&self, directive: &Directive) -> Result<(), String> {
+        if directive.argument.is_empty() {
+            return Ok(());
+        }
+        return Err(directive_error(
+            self.source,
+            directive.line,
+            &format!(
+                "'{}{}' does not take an argument",
+                self.prefix, directive.keyword
+            ),
+        ));
+    }
+}
+
+/// Parses the argument of an `#each` directive, e.g. `HOST in $BACKENDS by ";"`.
+///
+/// # Returns
+///
+/// * `Option<(&str, &str, Option<&str>)>` - The name of the loop variable, the list and the
+///   unquoted separator, or `None` if the argument does not have the form `NAME in LIST`.
+///
+fn parse_loop(argument: &str) -> Option<(&str, &str, Option<&str>)> {
+    let (name, rest) = argument.split_once(char::is_whitespace)?;
+    let (keyword, list) = rest
+        .trim_start()
+        .split_once(char::is_whitespace)
+        .unwrap_or((rest.trim_start(), ""));
+    if keyword != "in" || list.trim().is_empty() {
+        return None;
+    }
+
+    // A quoted separator can follow the list, e.g. 'by ";"'
+    if let Some((head, tail)) = list.rsplit_once(" by ") {
+        if let Some((separator, _)) = strip_quotes(tail.trim()) {
+            if !head.trim().is_empty() {
+                return Some((name, head.trim(), Some(separator)));
+            }
+        }
+    }
+
+    return Some((name, list.trim(), None));
+}
+
+/// Removes one pair of matching single or double quotes around `value`.
+///
+/// # Returns
+///
+/// * `Option<(&str, bool)>` - The unquoted value and `true` if it was enclosed in single quotes,
+///   or `None` if `value` is not quoted.
+///
+fn strip_quotes(value: &str) -> Option<(&str, bool)> {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return Some((inner, quote == '\''));
+        }
+    }
+    return None;
+}
+
+/// Expands `value` like a default value, unless it is enclosed in single quotes.
+///
+/// Surrounding double quotes are removed, e.g. to keep whitespace.
+fn expand_value(
+    value: &str,
+    flags: &Flags,
+    filters: &Filters,
+    variables: &mut Variables,
+) -> Result<String, String> {
+    return match strip_quotes(value) {
+        Some((inner, true)) => Ok(inner.to_string()),
+        Some((inner, false)) => expand_argument(inner, flags, filters, variables, false),
+        None => expand_argument(value, flags, filters, variables, false),
+    };
+}
+
+/// `Renderer` evaluates the block directives of a template and replaces the variables in the
+/// remaining lines.
+///
+/// The following directives are supported, shown with the default prefix `#`:
+/// * `#if CONDITION` starts a block that is only rendered if the condition is true.
+/// * `#else` starts the block that is rendered if the condition is false.
+/// * `#endif` ends the `#if` block.
+/// * `#each NAME in LIST` repeats the following lines for every item of the list, with `$NAME`
+///   set to the item.
+/// * `#end` ends the `#each` block.
+/// * `#include PATH` renders the template file `PATH` in place of the directive.
+///
+/// The directive lines themselves are removed from the output.
+pub struct Renderer<'a> {
+    flags: &'a Flags,
+    filters: &'a Filters,
+    settings: &'a Settings,
+    variables: &'a mut Variables,
+    /// The canonical path of the file that is rendered, or `None` for stdin.
+    file: Option<PathBuf>,
+    /// The name of the included file that is rendered, or `None` for the input.
+    source: Option<String>,
+    /// The canonical paths of the files that are rendered, to detect include cycles.
+    includes: Vec<PathBuf>,
+}
+
+impl<'a> Renderer<'a> {
+    /// Creates a `Renderer` that uses the given flags, filters and settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `flags` - The flags to use during variable replacement.
+    /// * `filters` - The filters to apply during variable replacement and in conditions.
+    /// * `settings` - The settings, e.g. with the directive prefix and the input file.
+    /// * `variables` - The variables of the current render.
+    ///
+    pub fn new(
+        flags: &'a Flags,
+        filters: &'a Filters,
+        settings: &'a Settings,
+        variables: &'a mut Variables,
+    ) -> Self {
+        let file = settings.input_file().and_then(|f| f.canonicalize().ok());
+        return Renderer {
+            flags,
+            filters,
+            settings,
+            variables,
+            includes: file.iter().cloned().collect(),
+            file,
+            source: None,
+        };
+    }
+
+    /// Renders the template `lines` and passes the output lines to `emit`.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - The lines of the template, including their line endings.
+    /// * `emit` - Receives the rendered lines.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), String>` - A `()` on success or an error message on failure.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directives are unbalanced or invalid, if a condition cannot be
+    /// evaluated, if there is an error in replacing variables, or if `emit` fails. Errors of
+    /// directives contain the line number.
+    ///
+    pub fn render_lines(
+        &mut self,
+        lines: &[String],
+        emit: &mut dyn FnMut(String) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let source = self.source.clone();
+        let mut parser = Parser {
+            lines,
+            prefix: self.settings.directive_prefix(),
+            source: source.as_deref(),
+            position: 0,
+        };
+        let (blocks, _) = parser.parse_blocks(&[])?;
+
+        return self.render(&blocks, emit);
+    }
+
+    /// Renders the parsed `blocks`.
+    fn render(
+        &mut self,
+        blocks: &[Block],
+        emit: &mut dyn FnMut(String) -> Result<(), String>,
+    ) -> Result<(), String> {
+        for block in blocks {
+            match block {
+                Block::Text(lines) => {
+                    let lines = lines.iter().map(|line| Ok(line.to_string()));
+                    replace_lines(
+                        lines,
+                        self.flags,
+                        self.filters,
+                        self.settings,
+                        self.variables,
+                        emit,
+                    )?;
+                }
+                Block::If {
+                    condition,
+                    line,
+                    then,
+                    otherwise,
+                } => {
+                    if self.evaluate_condition(condition, *line)? {
+                        self.render(then, emit)?;
+                    } else {
+                        self.render(otherwise, emit)?;
+                    }
+                }
+                Block::Each {
+                    name,
+                    list,
+                    separator,
+                    line,
+                    body,
+                } => {
+                    self.render_each(name, list, *separator, *line, body, emit)?;
+                }
+                Block::Include { path, line } => {
+                    self.render_include(path, *line, emit)?;
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Renders the `body` of the `#each` directive on `line` once for every item of the `list`.
+    ///
+    /// The `list` is expanded like the value of a condition and split on the `separator`, or on
+    /// commas and whitespace if no separator is given. The items are trimmed and empty items are
+    /// skipped. The loop variable `name` is set to the item while rendering the body and restored
+    /// afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the loop variable is excluded by the filters, if the list cannot be
+    /// expanded, or if rendering the body fails.
+    ///
+    fn render_each(
+        &mut self,
+        name: &str,
+        list: &str,
+        separator: Option<&str>,
+        line: usize,
+        body: &[Block],
+        emit: &mut dyn FnMut(String) -> Result<(), String>,
+    ) -> Result<(), String> {
+        if self.filters.matches(name) == Some(false) {
+            return Err(directive_error(
+                self.source.as_deref(),
+                line,
+                &format!("variable '{name}' is excluded by the filters"),
+            ));
+        }
+
+        let list = expand_value(list, self.flags, self.filters, self.variables)
+            .map_err(|e| directive_error(self.source.as_deref(), line, &e))?;
+        let items: Vec<&str> = match separator {
+            Some(separator) => list.split(separator).collect(),
+            None => list
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .collect(),
+        };
+
+        // Restore a value assigned before the loop, e.g. by '${HOST:=...}', afterwards
+        let previous = self.variables.remove(name);
+
+        for item in items
+            .iter()
+            .map(|item| item.trim())
+            .filter(|item| !item.is_empty())
+        {
+            self.variables.set(name, item);
+            self.render(body, emit)?;
+        }
+
+        self.variables.remove(name);
+        if let Some(previous) = previous {
+            self.variables.set(name, &previous);
+        }
+
+        return Ok(());
+    }
+
+    /// Renders the file included by the `#include` directive on `line`.
+    ///
+    /// The `path` is expanded like the value of a condition and resolved relative to the
+    /// directory of the including file, or to the current working directory for stdin. The file
+    /// is rendered with the same flags, filters and variables, and can contain directives itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, if it is not located in the include root, if
+    /// it is already being rendered (an include cycle), or if rendering it fails.
+    ///
+    fn render_include(
+        &mut self,
+        path: &str,
+        line: usize,
+        emit: &mut dyn FnMut(String) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let path = expand_value(path, self.flags, self.filters, self.variables)
+            .map_err(|e| directive_error(self.source.as_deref(), line, &e))?;
+
+        let directory = self
+            .file
+            .as_deref()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new("."));
+        let target = directory.join(&path);
+        let file = target.canonicalize().map_err(|e| {
+            return directive_error(
+                self.source.as_deref(),
+                line,
+                &format!("failed to include '{path}': {e}"),
+            );
+        })?;
+
+        let root = self.settings.include_root();
+        let canonical_root = root.canonicalize().map_err(|e| {
+            return directive_error(
+                self.source.as_deref(),
+                line,
+                &format!("invalid include root '{}': {e}", root.display()),
+            );
+        })?;
+        if !file.starts_with(&canonical_root) {
+            return Err(directive_error(
+                self.source.as_deref(),
+                line,
+                &format!(
+                    "'{path}' is outside of the include root '{}'",
+                    root.display()
+                ),
+            ));
+        }
+
+        if self.includes.contains(&file) {
+            return Err(directive_error(
+                self.source.as_deref(),
+                line,
+                &format!("'{path}' is included recursively"),
+            ));
+        }
+
+        let lines: Vec<String> = File::open(&file)
+            .and_then(|f| read_lines(BufReader::new(f)).collect())
+            .map_err(|e| {
+                return directive_error(
+                    self.source.as_deref(),
+                    line,
+                    &format!("failed to include '{path}': {e}"),
+                );
+            })?;
+
+        // Render the included file in its own context and restore the context afterwards
+        let parent_file = self.file.replace(file.clone());
+        let parent_source = self.source.replace(target.display().to_string());
+        self.includes.push(file);
+
+        let result = self.render_lines(&lines, emit);
+
+        self.includes.pop();
+        self.file = parent_file;
+        self.source = parent_source;
+
+        return result;
+    }
+
+    /// Evaluates the `condition` of the `#if` directive on `line`.
+    ///
+    /// The following conditions are supported:
+    /// * `VAR` is true if the variable is set and not empty.
+    /// * `!VAR` is true if the variable is unset or empty.
+    /// * `VAR == value` is true if the value of the variable equals `value`. An unset variable
+    ///   equals the empty string.
+    /// * `VAR != value` is the negation of `VAR == value`.
+    ///
+    /// The `value` can be enclosed in double quotes, e.g. to keep surrounding whitespace, and can
+    /// contain `$VAR` and `${...}` expressions. Values in single quotes are not expanded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the condition is invalid, if the variable is excluded by the filters,
+    /// or if the value cannot be expanded.
+    ///
+    fn evaluate_condition(&mut self, condition: &str, line: usize) -> Result<bool, String> {
+        let comparison = ["==", "!="]
+            .iter()
+            .filter_map(|op| condition.find(op).map(|index| (index, *op)))
+            .min();
+
+        let (name, negated, expected) = match comparison {
+            Some((index, op)) => (
+                condition[..index].trim(),
+                op == "!=",
+                Some(condition[index + op.len()..].trim()),
+            ),
+            None => match condition.strip_prefix('!') {
+                Some(name) => (name.trim(), true, None),
+                None => (condition, false, None),
+            },
+        };
+
+        if !is_valid_name(name) {
+            return Err(directive_error(
+                self.source.as_deref(),
+                line,
+                &format!("invalid condition '{condition}'"),
+            ));
+        }
+
+        if self.filters.matches(name) == Some(false) {
+            return Err(directive_error(
+                self.source.as_deref(),
+                line,
+                &format!("variable '{name}' is excluded by the filters"),
+            ));
+        }
+
+        let value = self.variables.get(name);
+
+        let result = match expected {
+            None => value.is_some_and(|v| !v.is_empty()),
+            Some(expected) => {
+                let expected = expand_value(expected, self.flags, self.filters, self.variables)
+                    .map_err(|e| directive_error(self.source.as_deref(), line, &e))?;
+                value.unwrap_or_default() == expected
+            }
+        };
+
+        return Ok(result != negated);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::Filter;
+    use crate::settings::Setting;
+    use std::env;
+    use std::fs;
+
+    fn render(template: &str, filters: &Filters, settings: &Settings) -> Result<String, String> {
+        let lines: Vec<String> = template.split_inclusive('\n').map(String::from).collect();
+        let flags = Flags::default();
+        let mut variables = Variables::default();
+        let mut output = String::new();
+        let mut renderer = Renderer::new(&flags, filters, settings, &mut variables);
+        renderer.render_lines(&lines, &mut |line| {
+            output.push_str(&line);
+            return Ok(());
+        })?;
+        return Ok(output);
+    }
+
+    #[test]
+    fn test_parse_directive() {
+        assert_eq!(
+            parse_directive("  #if TLS_ENABLED \n", 3, "#"),
+            Some(Directive {
+                keyword: "if",
+                argument: "TLS_ENABLED",
+                line: 3
+            })
+        );
+        assert_eq!(
+            parse_directive("#endif", 1, "#"),
+            Some(Directive {
+                keyword: "endif",
+                argument: "",
+                line: 1
+            })
+        );
+        assert_eq!(parse_directive("# if needed\n", 1, "#"), None);
+        assert_eq!(parse_directive("#ifdef X\n", 1, "#"), None);
+        assert_eq!(parse_directive("#if X\n", 1, "%"), None);
+    }
+
+    #[test]
+    fn test_render_if() {
+        env::set_var("DIRECTIVES_TLS", "true");
+        env::set_var("DIRECTIVES_EMPTY", "");
+        env::remove_var("DIRECTIVES_UNSET");
+        let (filters, settings) = (Filters::default(), Settings::default());
+
+        let template = "a\n#if DIRECTIVES_TLS\ntls: $DIRECTIVES_TLS\n#endif\nb\n";
+        assert_eq!(
+            render(template, &filters, &settings),
+            Ok("a\ntls: true\nb\n".to_string())
+        );
+
+        let template = "#if DIRECTIVES_EMPTY\nx\n#else\ny\n#endif\n";
+        assert_eq!(render(template, &filters, &settings), Ok("y\n".to_string()));
+
+        let template = "#if !DIRECTIVES_UNSET\nx\n#else\ny\n#endif";
+        assert_eq!(render(template, &filters, &settings), Ok("x\n".to_string()));
+    }
+
+    #[test]
+    fn test_render_comparison() {
+        env::set_var("DIRECTIVES_MODE", "prod");
+        env::set_var("DIRECTIVES_EXPECTED", "prod");
+        let (filters, settings) = (Filters::default(), Settings::default());
+
+        let cases = [
+            ("DIRECTIVES_MODE == prod", true),
+            ("DIRECTIVES_MODE==dev", false),
+            ("DIRECTIVES_MODE != dev", true),
+            ("DIRECTIVES_MODE == \"prod\"", true),
+            ("DIRECTIVES_MODE == $DIRECTIVES_EXPECTED", true),
+            ("DIRECTIVES_MODE == '$DIRECTIVES_EXPECTED'", false),
+            ("DIRECTIVES_MODE == ${DIRECTIVES_UNSET_MODE:-prod}", true),
+            ("DIRECTIVES_UNSET_MODE == \"\"", true),
+        ];
+        for (condition, expected) in cases {
+            let template = format!("#if {condition}\nx\n#endif\n");
+            let output = if expected { "x\n" } else { "" };
+            assert_eq!(
+                render(&template, &filters, &settings),
+                Ok(output.to_string()),
+                "{condition}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_nested() {
+        env::set_var("DIRECTIVES_OUTER", "1");
+        env::remove_var("DIRECTIVES_INNER");
+        let (filters, settings) = (Filters::default(), Settings::default());
+
+        let template = "\
+#if DIRECTIVES_OUTER
+  #if DIRECTIVES_INNER
+inner
+  #else
+outer only
+  #endif
+#else
+none
+#endif
+";
+        assert_eq!(
+            render(template, &filters, &settings),
+            Ok("outer only\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_loop() {
+        assert_eq!(
+            parse_loop("HOST in $BACKENDS"),
+            Some(("HOST", "$BACKENDS", None))
+        );
+        assert_eq!(
+            parse_loop("HOST  in  a b  by \";\""),
+            Some(("HOST", "a b", Some(";")))
+        );
+        assert_eq!(
+            parse_loop("X in stand by me"),
+            Some(("X", "stand by me", None))
+        );
+        assert_eq!(parse_loop("HOST in"), None);
+        assert_eq!(parse_loop("HOST of $BACKENDS"), None);
+        assert_eq!(parse_loop("HOST"), None);
+    }
+
+    #[test]
+    fn test_render_each() {
+        env::set_var("DIRECTIVES_BACKENDS", "app1:8080, app2:8080,,app3:8080");
+        env::set_var("DIRECTIVES_TARGETS", "a b;c d");
+        env::set_var("DIRECTIVES_HOST", "env");
+        let (filters, settings) = (Filters::default(), Settings::default());
+
+        let template = "\
+upstream app {
+#each DIRECTIVES_HOST in $DIRECTIVES_BACKENDS
+  server $DIRECTIVES_HOST;
+#end
+}
+$DIRECTIVES_HOST
+";
+        assert_eq!(
+            render(template, &filters, &settings),
+            Ok(
+                "upstream app {\n  server app1:8080;\n  server app2:8080;\n  server app3:8080;\n}\nenv\n"
+                    .to_string()
+            )
+        );
+
+        let template = "#each T in ${DIRECTIVES_TARGETS} by \";\"\n- '$T'\n#end\n";
+        assert_eq!(
+            render(template, &filters, &settings),
+            Ok("- 'a b'\n- 'c d'\n".to_string())
+        );
+
+        // Loops can be nested and combined with conditions
+        let template = "\
+#each A in 1 2
+#each B in x,y
+#if B == x
+$A$B
+#end
+#end
+#end
+";
+        assert_eq!(
+            render(template, &filters, &settings),
+            Err("failed to process directives: line 5: unexpected '#end'".to_string())
+        );
+        let template = template.replacen("#end\n", "#endif\n", 1);
+        assert_eq!(
+            render(&template, &filters, &settings),
+            Ok("1x\n2x\n".to_string())
+        );
+
+        let template = "#each X in ${DIRECTIVES_EMPTY_LIST:-}\nx\n#end\n";
+        assert_eq!(render(template, &filters, &settings), Ok(String::new()));
+    }
+
+    #[test]
+    fn test_render_each_restores_assigned_value() {
+        let filters = Filters::default();
+        let settings = Settings::default();
+        let template = "${DIRECTIVES_ITEM:=before}\n#each DIRECTIVES_ITEM in a\n$DIRECTIVES_ITEM\n#end\n$DIRECTIVES_ITEM\n";
+        assert_eq!(
+            render(template, &filters, &settings),
+            Ok("before\na\nbefore\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_render_prefix() {
+        env::set_var("DIRECTIVES_PREFIX", "1");
+        let filters = Filters::default();
+        let mut settings = Settings::default();
+        settings
+            .set(
+                Setting::DirectivePrefix,
+                "--directive-prefix",
+                Some("@@"),
+                &mut [].iter(),
+            )
+            .unwrap();
+
+        let template = "#if DIRECTIVES_PREFIX\n@@if DIRECTIVES_PREFIX\nx\n@@endif\n";
+        assert_eq!(
+            render(template, &filters, &settings),
+            Ok("#if DIRECTIVES_PREFIX\nx\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_render_errors() {
+        let (filters, settings) = (Filters::default(), Settings::default());
+
+        let cases = [
+            ("a\n#else\n", "line 2: unexpected '#else'"),
+            ("#endif\n", "line 1: unexpected '#endif'"),
+            ("a\n#if X\nb\n", "line 2: '#if' is not closed with '#endif'"),
+            (
+                "#if X\n#if Y\n#endif\n",
+                "line 1: '#if' is not closed with '#endif'",
+            ),
+            (
+                "#if X\n#else\n#else\n#endif\n",
+                "line 3: unexpected '#else'",
+            ),
+            (
+                "#if X\n#endif X\n",
+                "line 2: '#endif' does not take an argument",
+            ),
+            ("#if\n#endif\n", "line 1: '#if' requires a condition"),
+            ("#if X Y\n#endif\n", "line 1: invalid condition 'X Y'"),
+            ("#if == x\n#endif\n", "line 1: invalid condition '== x'"),
+            ("#each X\n#end\n", "line 1: '#each' requires 'NAME in LIST'"),
+            (
+                "#each 1X in a\n#end\n",
+                "line 1: invalid loop variable '1X'",
+            ),
+            (
+                "#each X in a by \"\"\n#end\n",
+                "line 1: the separator must not be empty",
+            ),
+            (
+                "#each X in a\n",
+                "line 1: '#each' is not closed with '#end'",
+            ),
+            ("#each X in a\n#endif\n", "line 2: unexpected '#endif'"),
+            ("#if X\n#end\n", "line 2: unexpected '#end'"),
+        ];
+        for (template, error) in cases {
+            assert_eq!(
+                render(template, &filters, &settings),
+                Err(format!("failed to process directives: {error}")),
+                "{template}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_include() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("fragments")).unwrap();
+        fs::write(
+            root.join("main.tmpl"),
+            "#include fragments/${DIRECTIVES_FRAGMENT}\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("fragments/tls.tmpl"),
+            "tls:\n#include cert.tmpl\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("fragments/cert.tmpl"),
+            "#if DIRECTIVES_CERT\n  cert: $DIRECTIVES_CERT\n#endif\n",
+        )
+        .unwrap();
+        env::set_var("DIRECTIVES_FRAGMENT", "tls.tmpl");
+        env::set_var("DIRECTIVES_CERT", "/etc/tls.crt");
+
+        let mut settings = Settings::default();
+        settings.set_input_file(root.join("main.tmpl").to_str().unwrap());
+
+        // Includes are resolved relative to the including file
+        let template = "a\n#include \"fragments/tls.tmpl\"\nb\n";
+        assert_eq!(
+            render(template, &Filters::default(), &settings),
+            Ok("a\ntls:\n  cert: /etc/tls.crt\nb\n".to_string())
+        );
+
+        let template = "#include fragments/${DIRECTIVES_FRAGMENT}\n";
+        assert_eq!(
+            render(template, &Filters::default(), &settings),
+            Ok("tls:\n  cert: /etc/tls.crt\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_render_include_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("templates");
+        fs::create_dir(&root).unwrap();
+        fs::write(dir.path().join("secret.txt"), "secret\n").unwrap();
+        fs::write(root.join("main.tmpl"), "#include a.tmpl\n").unwrap();
+        fs::write(root.join("a.tmpl"), "a\n#include b.tmpl\n").unwrap();
+        fs::write(root.join("b.tmpl"), "b\n#include main.tmpl\n").unwrap();
+        fs::write(root.join("else.tmpl"), "#else\n").unwrap();
+
+        let mut settings = Settings::default();
+        settings.set_input_file(root.join("main.tmpl").to_str().unwrap());
+        let filters = Filters::default();
+
+        let result = render("#include a.tmpl\n", &filters, &settings);
+        assert_eq!(
+            result,
+            Err(format!(
+                "failed to process directives: {}: line 2: 'main.tmpl' is included recursively",
+                root.join("b.tmpl").display()
+            ))
+        );
+
+        let result = render("x\n#include ../secret.txt\n", &filters, &settings);
+        assert_eq!(
+            result,
+            Err(format!(
+                "failed to process directives: line 2: '../secret.txt' is outside of the include root '{}'",
+                root.display()
+            ))
+        );
+
+        let result = render("#include else.tmpl\n", &filters, &settings);
+        assert_eq!(
+            result,
+            Err(format!(
+                "failed to process directives: {}: line 1: unexpected '#else'",
+                root.join("else.tmpl").display()
+            ))
+        );
+
+        let result = render("#include missing.tmpl\n", &filters, &settings);
+        assert!(result.unwrap_err().starts_with(
+            "failed to process directives: line 1: failed to include 'missing.tmpl': "
+        ));
+
+        // The include root can contain the parent directory
+        settings
+            .set(
+                Setting::IncludeRoot,
+                "--include-root",
+                Some(dir.path().to_str().unwrap()),
+                &mut [].iter(),
+            )
+            .unwrap();
+        let result = render("#include ../secret.txt\n", &filters, &settings);
+        assert_eq!(result, Ok("secret\n".to_string()));
+    }
+
+    #[test]
+    fn test_render_filtered_variable_error() {
+        let mut filters = Filters::default();
+        filters
+            .add(Filter::Prefix, "--prefix", Some("APP_"), &mut [].iter())
+            .unwrap();
+
+        assert_eq!(
+            render("#if DIRECTIVES_OTHER\n#endif\n", &filters, &Settings::default()),
+            Err(
+                "failed to process directives: line 1: variable 'DIRECTIVES_OTHER' is excluded by the filters"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use crate::variable_expansion::is_valid_name;
+use std::path::Path;
+
+/// Parses the content of a dotenv file into a list of variables.
+///
+/// The supported syntax is:
+/// * `NAME=value`, optionally prefixed with `export`, with whitespace around the `=`.
+/// * Empty lines and comments starting with `#`. Unquoted values end at a `#` preceded by
+///   whitespace, e.g. `PORT=8080 # comment`.
+/// * Single-quoted values, which are taken literally.
+/// * Double-quoted values, in which `\n`, `\r`, `\t`, `\"`, `\\` and `\$` are escape sequences.
+///
+/// Quoted values can span multiple lines. Values are not expanded, e.g. `$HOME` is kept as is.
+///
+/// # Arguments
+///
+/// * `content`: The content of the dotenv file.
+///
+/// # Returns
+///
+/// A `Result<Vec<(String, String)>, String>` containing the names and values in the order of the
+/// file, or an error message with the line number if the content is invalid.
+///
+/// # Errors
+///
+/// This function returns an error if a line is not an assignment, if a name is not valid, if a
+/// quoted value is not closed, or if a quoted value is followed by anything but a comment.
+///
+pub fn parse(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut variables = Vec::new();
+    let mut lines = content.lines().enumerate();
+
+    while let Some((index, line)) = lines.next() {
+        let number = index + 1;
+        let line = line.trim_start();
+
+        if line.trim_end().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line
+            .strip_prefix("export")
+            .filter(|rest| rest.starts_with(char::is_whitespace))
+            .map_or(line, str::trim_start);
+
+        let Some((name, value)) = line.split_once('=') else {
+            return Err(format!("line {number}: expected 'NAME=VALUE'"));
+        };
+
+        let name = name.trim_end();
+        if !is_valid_name(name) {
+            return Err(format!("line {number}: invalid variable name '{name}'"));
+        }
+
+        let value = value.trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('\'' | '"')) => {
+                // A quoted value may span multiple lines, so read lines until it is closed
+                let mut text = value[1..].to_string();
+                loop {
+                    if let Some((value, rest)) = parse_quoted(&text, quote) {
+                        let rest = rest.trim_start();
+                        if !rest.is_empty() && !rest.starts_with('#') {
+                            return Err(format!(
+                                "line {number}: unexpected '{rest}' after the quoted value"
+                            ));
+                        }
+                        break value;
+                    }
+                    let Some((_, next)) = lines.next() else {
+                        return Err(format!("line {number}: unterminated quoted value"));
+                    };
+                    text.push('\n');
+                    text.push_str(next);
+                }
+            }
+            _ => strip_comment(value).trim_end().to_string(),
+        };
+
+        variables.push((name.to_string(), value));
+    }
+
+    return Ok(variables);
+}
+
+/// Parses a quoted value up to the closing `quote`.
+///
+/// # Arguments
+///
+/// * `text`: The text after the opening quote.
+/// * `quote`: The quote character, `'` or `"`.
+///
+/// # Returns
+///
+/// An `Option<(String, &str)>` containing the unquoted value and the text after the closing quote,
+/// or `None` if the value is not closed.
+///
+fn parse_quoted(text: &str, quote: char) -> Option<(String, &str)> {
+    if quote == '\'' {
+        let end = text.find('\'')?;
+        return Some((text[..end].to_string(), &text[end + 1..]));
+    }
+
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &text[index + 1..])),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 'r')) => value.push('\r'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, escaped @ ('"' | '\\' | '$'))) => value.push(escaped),
+                // Unknown escape sequences are kept as is
+                Some((_, other)) => {
+                    value.push('\\');
+                    value.push(other);
+                }
+                None => value.push('\\'),
+            },
+            _ => value.push(c),
+        }
+    }
+
+    return None;
+}
+
+/// Removes a trailing comment from an unquoted value.
+///
+/// A comment starts with a `#` that is preceded by whitespace, so `a#b` is kept as is.
+fn strip_comment(value: &str) -> &str {
+    let mut previous_whitespace = false;
+    for (index, c) in value.char_indices() {
+        if c == '#' && previous_whitespace {
+            return &value[..index];
+        }
+        previous_whitespace = c.is_whitespace();
+    }
+    return value;
+}
+
+/// Reads and parses the dotenv file at `path`.
+///
+/// # Arguments
+///
+/// * `path`: The path of the dotenv file.
+///
+/// # Returns
+///
+/// A `Result<Vec<(String, String)>, String>` containing the names and values in the order of the
+/// file.
+///
+/// # Errors
+///
+/// This function returns an error if the file cannot be read or is not a valid dotenv file.
+///
+pub fn load_file(path: &Path) -> Result<Vec<(String, String)>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read env file '{}': {e}", path.display()))?;
+    return parse(&content)
+        .map_err(|e| format!("failed to parse env file '{}': {e}", path.display()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(content: &str) -> Vec<(String, String)> {
+        return parse(content).unwrap();
+    }
+
+    fn pair(name: &str, value: &str) -> (String, String) {
+        return (name.to_string(), value.to_string());
+    }
+
+    #[test]
+    fn test_parse_unquoted() {
+        let content = "# comment\n\nHOST=localhost\n  PORT = 8080 # port\nexport USER=admin\n\
+                       EMPTY=\nHASH=a#b\nexported=1\n";
+        assert_eq!(
+            pairs(content),
+            vec![
+                pair("HOST", "localhost"),
+                pair("PORT", "8080"),
+                pair("USER", "admin"),
+                pair("EMPTY", ""),
+                pair("HASH", "a#b"),
+                pair("exported", "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_quoted() {
+        let content = "SINGLE='it is $HOME'\nDOUBLE=\"say \\\"hi\\\"\\n\\$HOME \\d\" # comment\n\
+                       SPACES='  padded  '\r\nEQUALS=\"a=b\"\n";
+        assert_eq!(
+            pairs(content),
+            vec![
+                pair("SINGLE", "it is $HOME"),
+                pair("DOUBLE", "say \"hi\"\n$HOME \\d"),
+                pair("SPACES", "  padded  "),
+                pair("EQUALS", "a=b"),
+            ]
+        );
+        assert_eq!(
+            pairs("SINGLE='$HOME \\n'"),
+            vec![pair("SINGLE", "$HOME \\n")]
+        );
+    }
+
+    #[test]
+    fn test_parse_multi_line() {
+        let content =
+            "KEY=\"-----BEGIN KEY-----\nabc\n-----END KEY-----\"\nNEXT='a\n\n  b'\nLAST=1";
+        assert_eq!(
+            pairs(content),
+            vec![
+                pair("KEY", "-----BEGIN KEY-----\nabc\n-----END KEY-----"),
+                pair("NEXT", "a\n\n  b"),
+                pair("LAST", "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            ("HOST=localhost\nPORT", "line 2: expected 'NAME=VALUE'"),
+            ("1HOST=localhost", "line 1: invalid variable name '1HOST'"),
+            ("export =value", "line 1: invalid variable name ''"),
+            ("A=1\nKEY=\"abc\n\ndef", "line 2: unterminated quoted value"),
+            (
+                "KEY='abc' def",
+                "line 1: unexpected 'def' after the quoted value",
+            ),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(parse(content), Err(expected.to_string()), "{content}");
+        }
+    }
+
+    #[test]
+    fn test_load_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        std::fs::write(&path, "HOST=localhost\nPORT=8080\n").unwrap();
+        assert_eq!(
+            load_file(&path).unwrap(),
+            vec![pair("HOST", "localhost"), pair("PORT", "8080")]
+        );
+
+        std::fs::write(&path, "INVALID\n").unwrap();
+        assert_eq!(
+            load_file(&path),
+            Err(format!(
+                "failed to parse env file '{}': line 1: expected 'NAME=VALUE'",
+                path.display()
+            ))
+        );
+
+        let missing = dir.path().join("missing.env");
+        assert!(load_file(&missing).unwrap_err().starts_with(&format!(
+            "failed to read env file '{}': ",
+            missing.display()
+        )));
+    }
+}
@@ -0,0 +1,276 @@
+use std::fmt::Write;
+
+/// The alphabet of the standard base64 encoding (RFC 4648, section 4).
+const BASE64_STANDARD: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The alphabet of the URL and filename safe base64 encoding (RFC 4648, section 5).
+const BASE64_URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes `data` as base64 with padding.
+///
+/// # Arguments
+///
+/// * `data`: The bytes to encode.
+/// * `url_safe`: `true` to use the URL and filename safe alphabet (`-` and `_` instead of `+` and `/`).
+///
+/// # Returns
+///
+/// The encoded string.
+///
+pub fn base64_encode(data: &[u8], url_safe: bool) -> String {
+    let alphabet = if url_safe {
+        BASE64_URL
+    } else {
+        BASE64_STANDARD
+    };
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+
+        // A chunk of n bytes results in n + 1 characters, the rest is padding
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                encoded.push(char::from(alphabet[index as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    return encoded;
+}
+
+/// Decodes base64 encoded `data`.
+///
+/// Padding is optional and ASCII whitespace, like line breaks, is ignored.
+///
+/// # Arguments
+///
+/// * `data`: The base64 encoded string.
+/// * `url_safe`: `true` to use the URL and filename safe alphabet (`-` and `_` instead of `+` and `/`).
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` - The decoded bytes.
+/// * `Err(String)` - An error string if `data` is not valid base64.
+pub fn base64_decode(data: &str, url_safe: bool) -> Result<Vec<u8>, String> {
+    let alphabet = if url_safe {
+        BASE64_URL
+    } else {
+        BASE64_STANDARD
+    };
+    let data: Vec<u8> = data.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+
+    // Remove up to two padding characters, if the length is a multiple of four
+    let mut end = data.len();
+    if end.is_multiple_of(4) {
+        while end > 0 && data.len() - end < 2 && data[end - 1] == b'=' {
+            end -= 1;
+        }
+    }
+    let data = &data[..end];
+
+    if data.len() % 4 == 1 {
+        return Err("Invalid base64: invalid length".to_string());
+    }
+
+    let mut decoded = Vec::with_capacity(data.len() * 3 / 4);
+    for chunk in data.chunks(4) {
+        let mut group: u32 = 0;
+        for (i, byte) in chunk.iter().enumerate() {
+            let (_, index) = alphabet
+                .iter()
+                .zip(0u32..)
+                .find(|(c, _)| *c == byte)
+                .ok_or_else(|| {
+                    format!("Invalid base64: invalid character '{}'", char::from(*byte))
+                })?;
+            group |= index << (18 - 6 * i);
+        }
+
+        // The group holds 24 bits, a chunk of n characters results in n - 1 bytes
+        decoded.extend_from_slice(&group.to_be_bytes()[1..chunk.len()]);
+    }
+
+    return Ok(decoded);
+}
+
+/// Encodes `data` as lowercase hexadecimal string.
+///
+/// # Arguments
+///
+/// * `data`: The bytes to encode.
+///
+/// # Returns
+///
+/// The encoded string.
+///
+pub fn hex_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len() * 2);
+    for byte in data {
+        // Writing to a string cannot fail
+        let _ = write!(encoded, "{byte:02x}");
+    }
+    return encoded;
+}
+
+/// Decodes a hexadecimal string. Uppercase and lowercase digits are accepted.
+///
+/// # Arguments
+///
+/// * `data`: The hexadecimal string.
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` - The decoded bytes.
+/// * `Err(String)` - An error string if `data` is not a valid hexadecimal string.
+pub fn hex_decode(data: &str) -> Result<Vec<u8>, String> {
+    if !data.len().is_multiple_of(2) {
+        return Err("Invalid hex: odd number of digits".to_string());
+    }
+
+    return data
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let digits = String::from_utf8_lossy(pair);
+            if !pair.iter().all(u8::is_ascii_hexdigit) {
+                return Err(format!("Invalid hex: invalid digits '{digits}'"));
+            }
+            return u8::from_str_radix(&digits, 16)
+                .map_err(|_| format!("Invalid hex: invalid digits '{digits}'"));
+        })
+        .collect();
+}
+
+/// Percent-encodes `data` for use in an URL (RFC 3986).
+///
+/// All bytes except the unreserved characters `A-Z`, `a-z`, `0-9`, `-`, `.`, `_` and `~` are
+/// encoded, so the result can be used in any part of an URL, e.g. as a password.
+///
+/// # Arguments
+///
+/// * `data`: The string to encode.
+///
+/// # Returns
+///
+/// The encoded string.
+///
+pub fn url_encode(data: &str) -> String {
+    let mut encoded = String::with_capacity(data.len());
+    for byte in data.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    return encoded;
+}
+
+/// Decodes a percent-encoded string.
+///
+/// # Arguments
+///
+/// * `data`: The percent-encoded string.
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` - The decoded bytes.
+/// * `Err(String)` - An error string if `data` contains an invalid percent-encoded sequence.
+pub fn url_decode(data: &str) -> Result<Vec<u8>, String> {
+    let bytes = data.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            decoded.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+
+        let sequence = bytes
+            .get(i + 1..i + 3)
+            .filter(|digits| digits.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| format!("Invalid percent-encoding at position {i}"))?;
+        decoded.push(sequence);
+        i += 3;
+    }
+
+    return Ok(decoded);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b"", false), "");
+        assert_eq!(base64_encode(b"f", false), "Zg==");
+        assert_eq!(base64_encode(b"fo", false), "Zm8=");
+        assert_eq!(base64_encode(b"foo", false), "Zm9v");
+        assert_eq!(base64_encode(b"foobar", false), "Zm9vYmFy");
+        assert_eq!(base64_encode(&[0xfb, 0xff], false), "+/8=");
+        assert_eq!(base64_encode(&[0xfb, 0xff], true), "-_8=");
+    }
+
+    #[test]
+    fn test_base64_decode() {
+        assert_eq!(base64_decode("Zm9vYmFy", false), Ok(b"foobar".to_vec()));
+        assert_eq!(base64_decode("Zg==", false), Ok(b"f".to_vec()));
+        assert_eq!(base64_decode("Zm8", false), Ok(b"fo".to_vec()));
+        assert_eq!(base64_decode("Zm9v\nYmFy\n", false), Ok(b"foobar".to_vec()));
+        assert_eq!(base64_decode("-_8=", true), Ok(vec![0xfb, 0xff]));
+        assert_eq!(
+            base64_decode("-_8=", false),
+            Err("Invalid base64: invalid character '-'".to_string())
+        );
+        assert_eq!(
+            base64_decode("Zm9vY", false),
+            Err("Invalid base64: invalid length".to_string())
+        );
+        assert!(base64_decode("Zg=a", false).is_err());
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(hex_encode(b"\x00\xffA"), "00ff41");
+        assert!(hex_decode("+1").is_err());
+        assert_eq!(hex_decode("00fF41"), Ok(b"\x00\xffA".to_vec()));
+        assert_eq!(
+            hex_decode("abc"),
+            Err("Invalid hex: odd number of digits".to_string())
+        );
+        assert_eq!(
+            hex_decode("zz"),
+            Err("Invalid hex: invalid digits 'zz'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_url() {
+        assert_eq!(url_encode("p@ss w/rd:ä~"), "p%40ss%20w%2Frd%3A%C3%A4~");
+        assert_eq!(
+            url_decode("p%40ss%20w%2Frd%3A%C3%A4~"),
+            Ok("p@ss w/rd:ä~".as_bytes().to_vec())
+        );
+        assert_eq!(
+            url_decode("100%"),
+            Err("Invalid percent-encoding at position 3".to_string())
+        );
+        assert!(url_decode("%G0").is_err());
+        assert!(url_decode("%+1").is_err());
+    }
+}
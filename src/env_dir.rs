@@ -0,0 +1,156 @@
+use crate::flags::{Flag, Flags};
+use crate::variable_expansion::{is_valid_name, lookup_name};
+use std::path::Path;
+
+/// Reads the variables of a directory with one file per variable, e.g. a mounted Kubernetes
+/// Secret or Docker secrets.
+///
+/// Every regular file becomes a variable named after the file and holding its content. Symbolic
+/// links are followed. Entries starting with `..` are skipped, e.g. the `..data` link Kubernetes
+/// uses to update the files atomically. Files whose names are not valid variable names are
+/// skipped as well, unless they are valid with `--relaxed-names`, e.g. `tls.crt` as `TLS_CRT`.
+///
+/// One trailing newline is removed from the content, unless `--keep-trailing-newline` is set.
+///
+/// # Arguments
+///
+/// * `path`: The path of the directory.
+/// * `flags`: Flags that affect the names and values of the variables.
+///
+/// # Returns
+///
+/// A `Result<Vec<(String, String)>, String>` containing the names and values, sorted by name.
+///
+/// # Errors
+///
+/// This function returns an error if the directory or a file cannot be read, or if the content of
+/// a file is not valid UTF-8.
+///
+pub fn load_dir(path: &Path, flags: &Flags) -> Result<Vec<(String, String)>, String> {
+    let read_error = |e: std::io::Error| {
+        return format!("failed to read env directory '{}': {e}", path.display());
+    };
+
+    let mut variables = Vec::new();
+
+    for entry in std::fs::read_dir(path).map_err(read_error)? {
+        let entry = entry.map_err(read_error)?;
+        let Ok(file_name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if file_name.starts_with("..") {
+            continue;
+        }
+
+        let name = lookup_name(&file_name, flags);
+        if !is_valid_name(&name) {
+            continue;
+        }
+
+        // 'metadata' follows symbolic links, unlike 'DirEntry::metadata'
+        let file_path = entry.path();
+        let file_error = |e: std::io::Error| {
+            return format!("failed to read env file '{}': {e}", file_path.display());
+        };
+        if !std::fs::metadata(&file_path).map_err(file_error)?.is_file() {
+            continue;
+        }
+
+        let content = std::fs::read_to_string(&file_path).map_err(file_error)?;
+        let value = if flags.is_flag_set(Flag::KeepTrailingNewline) {
+            content
+        } else {
+            let trimmed = content.strip_suffix('\n').unwrap_or(&content);
+            trimmed.strip_suffix('\r').unwrap_or(trimmed).to_string()
+        };
+
+        variables.push((name, value));
+    }
+
+    variables.sort();
+    return Ok(variables);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(name: &str, value: &str) -> (String, String) {
+        return (name.to_string(), value.to_string());
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("USER"), "admin\n").unwrap();
+        std::fs::write(dir.path().join("PASSWORD"), "secret\r\n").unwrap();
+        std::fs::write(dir.path().join("KEY"), "line 1\nline 2\n\n").unwrap();
+        std::fs::write(dir.path().join("EMPTY"), "").unwrap();
+        std::fs::write(dir.path().join("tls.crt"), "certificate").unwrap();
+        std::fs::create_dir(dir.path().join("NESTED")).unwrap();
+
+        let flags = Flags::default();
+        assert_eq!(
+            load_dir(dir.path(), &flags).unwrap(),
+            vec![
+                pair("EMPTY", ""),
+                pair("KEY", "line 1\nline 2\n"),
+                pair("PASSWORD", "secret"),
+                pair("USER", "admin"),
+            ]
+        );
+
+        let mut flags = Flags::default();
+        flags
+            .set(Flag::RelaxedNames, "--relaxed-names", true)
+            .unwrap();
+        flags
+            .set(Flag::KeepTrailingNewline, "--keep-trailing-newline", true)
+            .unwrap();
+        let variables = load_dir(dir.path(), &flags).unwrap();
+        assert!(variables.contains(&pair("TLS_CRT", "certificate")));
+        assert!(variables.contains(&pair("PASSWORD", "secret\r\n")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_load_dir_kubernetes_secret() {
+        use std::os::unix::fs::symlink;
+
+        // Kubernetes mounts the keys as links to '..data', which links to a timestamped directory
+        let dir = tempfile::tempdir().unwrap();
+        let timestamped = dir.path().join("..2024_01_01_00_00_00.000000000");
+        std::fs::create_dir(&timestamped).unwrap();
+        std::fs::write(timestamped.join("PASSWORD"), "secret\n").unwrap();
+        symlink(&timestamped, dir.path().join("..data")).unwrap();
+        symlink("..data/PASSWORD", dir.path().join("PASSWORD")).unwrap();
+        symlink("..data/MISSING", dir.path().join("MISSING")).unwrap();
+
+        let result = load_dir(dir.path(), &Flags::default());
+        assert!(result.unwrap_err().starts_with(&format!(
+            "failed to read env file '{}': ",
+            dir.path().join("MISSING").display()
+        )));
+
+        std::fs::remove_file(dir.path().join("MISSING")).unwrap();
+        assert_eq!(
+            load_dir(dir.path(), &Flags::default()).unwrap(),
+            vec![pair("PASSWORD", "secret")]
+        );
+    }
+
+    #[test]
+    fn test_load_dir_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        let result = load_dir(&missing, &Flags::default());
+        assert!(result.unwrap_err().starts_with(&format!(
+            "failed to read env directory '{}': ",
+            missing.display()
+        )));
+
+        std::fs::write(dir.path().join("BINARY"), [0xff, 0xfe]).unwrap();
+        let result = load_dir(dir.path(), &Flags::default());
+        assert!(result.unwrap_err().starts_with("failed to read env file"));
+    }
+}
@@ -1,11 +1,24 @@
+use crate::directives::Renderer;
 use crate::filters::Filters;
 use crate::flags::{Flag, Flags};
+use crate::glob;
+use crate::settings::{Dialect, Settings};
 use crate::utils::{colorize_text, handle_flags_on_result};
-use crate::variable_expansion::process_inner_expression;
+use crate::variable_expansion::{
+    expand_inner_expression, lookup_name, name_length, process_arithmetic,
+    process_inner_expression, Expansion,
+};
+use crate::variables::Variables;
 use colored::Color;
-use std::env;
+use std::collections::VecDeque;
 use std::io::{BufRead, BufReader};
 
+/// The maximum number of nested expressions, e.g. `${A:-${B:-${C}}}` has two nested expressions.
+///
+/// Nested expressions are expanded recursively, so deeper expressions fail with an error instead
+/// of overflowing the stack.
+const MAX_NESTING_DEPTH: usize = 100;
+
 /// Reads lines from a buffered reader and returns an iterator over the lines.
 ///
 /// This function takes a mutable reference to an object implementing the `BufRead` trait,
@@ -21,7 +34,7 @@ use std::io::{BufRead, BufReader};
 ///   Each item in the iterator is a `std::io::Result<String>`, where the `Ok` variant contains a line,
 ///   and the `Err` variant contains an error that occurred while reading.
 ///
-fn read_lines(mut input: impl BufRead) -> impl Iterator<Item = std::io::Result<String>> {
+pub fn read_lines(mut input: impl BufRead) -> impl Iterator<Item = std::io::Result<String>> {
     std::iter::from_fn(move || {
         let mut vec = String::new();
         match input.read_line(&mut vec) {
@@ -47,17 +60,77 @@ fn read_lines(mut input: impl BufRead) -> impl Iterator<Item = std::io::Result<S
 /// * line - A string slice containing the line of text to replace variables in.
 /// * flags - A reference to a Flags object containing the flags to use during variable replacement.
 /// * filters - A reference to a Filters object containing the filters to apply during variable replacement.
+/// * variables - The variables of the current render, shared by all lines of the input.
 ///
 /// # Returns
 ///
 /// * Result<String, String> - A Result containing the modified line of text as a String on success
-/// or an error message as a String on failure.
+///   or an error message as a String on failure.
 ///
 /// # Errors
 ///
 /// This function will return an error if a variable name is invalid or if an invalid flag is encountered.
 ///
-fn replace_vars_in_line(line: &str, flags: &Flags, filters: &Filters) -> Result<String, String> {
+fn replace_vars_in_line(
+    line: &str,
+    flags: &Flags,
+    filters: &Filters,
+    variables: &mut Variables,
+) -> Result<String, String> {
+    return replace_vars(line, flags, filters, variables, false);
+}
+
+/// Expands the variables in an operator argument, e.g. the default value of `${VAR:-${OTHER}}`.
+///
+/// The argument is expanded with the same flags and filters as the surrounding expression, but
+/// without colors, as the result is colorized as a whole by the enclosing expression.
+///
+/// # Arguments
+///
+/// * `argument` - The operator argument to expand.
+/// * `flags` - The flags to use during variable replacement.
+/// * `filters` - The filters to apply during variable replacement.
+/// * `variables` - The variables of the current render.
+/// * `literal` - If `true`, the expanded values are escaped so that they are matched literally when
+///   the argument is used as a glob pattern or as a replacement string.
+///
+/// # Returns
+///
+/// * `Result<String, String>` - The expanded argument, or an error message if the expansion fails.
+///
+pub fn expand_argument(
+    argument: &str,
+    flags: &Flags,
+    filters: &Filters,
+    variables: &mut Variables,
+    literal: bool,
+) -> Result<String, String> {
+    if !argument.contains('$') {
+        return Ok(argument.to_string());
+    }
+
+    let mut flags = flags.clone();
+    flags.update(Flag::Color, false);
+
+    return replace_vars(argument, &flags, filters, variables, literal);
+}
+
+/// Replaces variables in `line`, see `replace_vars_in_line`.
+///
+/// If `literal` is `true`, the substituted values are escaped with `glob::escape`.
+fn replace_vars(
+    line: &str,
+    flags: &Flags,
+    filters: &Filters,
+    variables: &mut Variables,
+    literal: bool,
+) -> Result<String, String> {
+    let escape = |value: String| -> String {
+        if literal {
+            return glob::escape(&value);
+        }
+        return value;
+    };
     let mut new_line: String = String::with_capacity(line.len());
     let mut iter = line.chars().peekable();
     let colored = flags.is_flag_set(Flag::Color);
@@ -70,10 +143,10 @@ fn replace_vars_in_line(line: &str, flags: &Flags, filters: &Filters) -> Result<
             continue;
         }
 
-        let next_char = iter.peek();
+        let next_char = iter.peek().copied();
 
         // Check if the current character is an escaped '$' and the next character is also '$'
-        if check_escape && c == '$' && next_char == Some(&'$') {
+        if check_escape && c == '$' && next_char == Some('$') {
             // Consume the next character (the second '$') in the iterator
             iter.next();
 
@@ -112,16 +185,23 @@ fn replace_vars_in_line(line: &str, flags: &Flags, filters: &Filters) -> Result<
 
                 let mut brace_ended = false;
                 let mut inner_expr: String = String::new();
+                // Number of nested '${' that are not closed yet, e.g. in ${A:-${B}}
+                let mut depth = 0;
+                let mut max_depth = 0;
 
-                // Read until the next '}' or the end of the line
-                while let Some(&c) = iter.peek() {
+                // Read until the matching '}' or the end of the line
+                for c in iter.by_ref() {
                     if c == '}' {
-                        iter.next(); // Consume '}'
-                        brace_ended = true;
-                        break;
+                        if depth == 0 {
+                            brace_ended = true;
+                            break;
+                        }
+                        depth -= 1;
+                    } else if c == '{' && inner_expr.ends_with('$') {
+                        depth += 1;
+                        max_depth = max_depth.max(depth);
                     }
                     inner_expr.push(c);
-                    iter.next();
                 }
 
                 if !brace_ended {
@@ -130,14 +210,56 @@ fn replace_vars_in_line(line: &str, flags: &Flags, filters: &Filters) -> Result<
                     continue;
                 }
 
+                if max_depth > MAX_NESTING_DEPTH {
+                    return Err(format!(
+                        "Expression is nested more than {MAX_NESTING_DEPTH} levels deep"
+                    ));
+                }
+
                 // Process inner expression here
-                let value = process_inner_expression(&inner_expr, flags, filters)?;
+                let value = process_inner_expression(&inner_expr, flags, filters, variables)?;
+
+                new_line.push_str(&escape(value));
+            }
+
+            // Handles $((EXPRESSION))
+            Some('(') if iter.clone().nth(1) == Some('(') => {
+                iter.next(); // skip the first '('
+                iter.next(); // skip the second '('
+
+                let mut expression = String::new();
+                let mut closed = false;
+                // Number of open parentheses inside of the expression
+                let mut depth = 0;
+
+                // Read until the closing '))' or the end of the line
+                while let Some(c) = iter.next() {
+                    match c {
+                        '(' => depth += 1,
+                        ')' if depth > 0 => depth -= 1,
+                        ')' if iter.peek() == Some(&')') => {
+                            iter.next(); // Consume the second ')'
+                            closed = true;
+                            break;
+                        }
+                        _ => {}
+                    }
+                    expression.push(c);
+                }
+
+                if !closed {
+                    // If the expression hasn't ended, add the characters and continue
+                    new_line.push_str("$((");
+                    new_line.push_str(&expression);
+                    continue;
+                }
 
-                new_line.push_str(&value);
+                let value = process_arithmetic(&expression, flags, filters, variables)?;
+                new_line.push_str(&escape(value));
             }
 
             // Handles $VAR and $VAR
-            Some(next) if next.is_ascii_alphabetic() || next == &'_' => {
+            Some(next) if next.is_ascii_alphabetic() || next == '_' => {
                 let mut var_name: String = String::new();
 
                 // Look ahead to see if the next character is valid
@@ -153,14 +275,20 @@ fn replace_vars_in_line(line: &str, flags: &Flags, filters: &Filters) -> Result<
                 let original_variable = format!("${var_name}");
 
                 if filters.matches(&var_name) == Some(false) {
-                    new_line.push_str(&colorize_text(colored, original_variable, Color::Magenta));
+                    new_line.push_str(&colorize_text(
+                        colored,
+                        escape(original_variable),
+                        Color::Magenta,
+                    ));
                     continue;
                 }
 
-                let value: String = env::var(&var_name).unwrap_or_default();
+                let value: String = variables
+                    .get(&lookup_name(&var_name, flags))
+                    .unwrap_or_default();
                 let result = handle_flags_on_result(value, &var_name, &original_variable, flags)?;
 
-                new_line.push_str(&colorize_text(colored, result, Color::Green));
+                new_line.push_str(&colorize_text(colored, escape(result), Color::Green));
             }
             // Everything else
             _ => {
@@ -172,10 +300,401 @@ fn replace_vars_in_line(line: &str, flags: &Flags, filters: &Filters) -> Result<
 
     return Ok(new_line);
 }
+
+/// Checks whether `inner`, the text between the delimiters of a dialect, is an expression.
+///
+/// The text must start with a variable name, optionally preceded by `#` or `!`, which is followed
+/// by the end of the text, by an operator or by a pipeline like ` | lower`. This keeps text like
+/// `50% of 100%`, `ping @alice and @bob` or `{{ range .Items }}` unchanged.
+///
+/// # Arguments
+///
+/// * `inner` - The text between the delimiters.
+/// * `relaxed` - Whether the name can contain dots and dashes, see `--relaxed-names`.
+///
+fn is_delimited_expression(inner: &str, relaxed: bool) -> bool {
+    let name = inner.strip_prefix(['#', '!']).unwrap_or(inner);
+    if !name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+    {
+        return false;
+    }
+
+    let rest = &name[name_length(name, relaxed)..];
+    let is_operator = |c: char| ":-=?+#%/,^|@*~".contains(c);
+    // Whitespace after the name is only allowed before the '|' of a pipeline, e.g. 'VAR | lower'
+    let is_pipeline = rest.starts_with(char::is_whitespace) && rest.trim_start().starts_with("| ");
+    return rest.is_empty() || rest.starts_with(is_operator) || is_pipeline;
+}
+
+/// Returns the length of the text that continues the expression before it, if the expression is
+/// followed by text that would be an operator containing the delimiter, e.g. `%.txt%` after
+/// `%FILE%` for `${FILE%%.txt}` or `Q@` after `@VAR@` for `${VAR@Q}`.
+///
+/// Such an expression cannot be told apart from an expression followed by text, because the
+/// delimiter ends the expression. Text that contains whitespace, a doubled delimiter and another
+/// expression like `%B%` in `%A%%B%` do not continue the expression.
+///
+/// # Arguments
+///
+/// * `after` - The text after the closing delimiter of the expression.
+/// * `delimiter` - The delimiter of the dialect, e.g. `%`.
+/// * `relaxed` - Whether names can contain dots and dashes, see `--relaxed-names`.
+///
+fn continued_length(after: &str, delimiter: &str, relaxed: bool) -> Option<usize> {
+    let next = after.find(delimiter)?;
+    let following = &after[next + delimiter.len()..];
+    if following.starts_with(delimiter) {
+        return None;
+    }
+
+    let operand = match following.find(delimiter) {
+        Some(end) if is_delimited_expression(&following[..end], relaxed) => return None,
+        Some(end) => &after[..next + delimiter.len() + end],
+        None => &after[..next],
+    };
+    if operand.is_empty() || operand.contains(char::is_whitespace) {
+        return None;
+    }
+    return Some(operand.len() + delimiter.len());
+}
+
+/// Finds the expression at the start of `text`, which starts with the opening delimiter of the
+/// `dialect`.
+///
+/// If the delimiters differ, e.g. `{{` and `}}`, nested delimiters and braced expressions like
+/// `${VAR}` are skipped to find the matching closing delimiter.
+///
+/// # Arguments
+///
+/// * `text` - The text starting with the opening delimiter.
+/// * `dialect` - The dialect that defines the delimiters.
+/// * `relaxed` - Whether names can contain dots and dashes, see `--relaxed-names`.
+///
+/// # Returns
+///
+/// * `Ok(Some((&str, usize)))` - The expression between the delimiters and the length of the
+///   expression including the delimiters.
+/// * `Ok(None)` - If `text` does not start with an expression, e.g. `50% of 100%`.
+/// * `Err(String)` - An error string if the expression contains nested delimiters, or if it is
+///   followed by text that would be an operator containing the delimiter, see `continued_length`.
+///
+fn find_delimited_expression(
+    text: &str,
+    dialect: Dialect,
+    relaxed: bool,
+) -> Result<Option<(&str, usize)>, String> {
+    let Some((open, close)) = dialect.delimiters() else {
+        return Ok(None);
+    };
+    let body = &text[open.len()..];
+
+    let mut end = None;
+    let mut nested = false;
+    if open == close {
+        end = body.find(close);
+    } else {
+        // Number of open nested delimiters and braced expressions
+        let (mut depth, mut braces) = (0, 0);
+        let mut index = 0;
+        while let Some(c) = body[index..].chars().next() {
+            let rest = &body[index..];
+            if rest.starts_with("${") {
+                braces += 1;
+                index += 2;
+                continue;
+            }
+            if c == '}' && braces > 0 {
+                braces -= 1;
+            } else if rest.starts_with(close) && depth == 0 {
+                end = Some(index);
+                break;
+            } else if rest.starts_with(close) {
+                depth -= 1;
+                index += close.len();
+                continue;
+            } else if rest.starts_with(open) {
+                depth += 1;
+                nested = true;
+                index += open.len();
+                continue;
+            }
+            index += c.len_utf8();
+        }
+    }
+
+    let Some(end) = end else {
+        return Ok(None);
+    };
+    let raw = &body[..end];
+    let inner = if dialect.allows_padding() {
+        raw.trim()
+    } else {
+        raw
+    };
+    if !is_delimited_expression(inner, relaxed) {
+        return Ok(None);
+    }
+
+    let length = open.len() + end + close.len();
+    if nested {
+        return Err(format!(
+            "\"{}\" - Nested '{open}' are not supported, use '${{...}}' for nested expressions",
+            &text[..length]
+        ));
+    }
+    if open == close {
+        if let Some(continued) = continued_length(&text[length..], close, relaxed) {
+            return Err(format!(
+                "\"{}\" - Ambiguous expression, operators cannot contain the delimiter '{close}'",
+                &text[..length + continued]
+            ));
+        }
+    }
+
+    return Ok(Some((inner, length)));
+}
+
+/// Replaces the variables in `line` using the delimiters of a `dialect` other than `Shell`,
+/// e.g. `{{VAR}}` or `@VAR:-default@`.
+///
+/// The text between the delimiters is processed like the content of `${...}`. Delimiters that do
+/// not enclose an expression are kept as is, a doubled opening delimiter like `%%` is a literal
+/// delimiter.
+///
+/// # Arguments
+///
+/// * line - The line of text to replace variables in.
+/// * dialect - The dialect that defines the delimiters.
+/// * flags - The flags to use during variable replacement.
+/// * filters - The filters to apply during variable replacement.
+/// * variables - The variables of the current render.
+///
+/// # Returns
+///
+/// * Result<String, String> - The line with the variables replaced, or an error message.
+///
+fn replace_delimited_vars(
+    line: &str,
+    dialect: Dialect,
+    flags: &Flags,
+    filters: &Filters,
+    variables: &mut Variables,
+) -> Result<String, String> {
+    let Some((open, _)) = dialect.delimiters() else {
+        return replace_vars_in_line(line, flags, filters, variables);
+    };
+    let relaxed = flags.is_flag_set(Flag::RelaxedNames);
+    let colored = flags.is_flag_set(Flag::Color);
+
+    let mut new_line = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(start) = rest.find(open) {
+        new_line.push_str(&rest[..start]);
+        let text = &rest[start..];
+
+        // A doubled delimiter is a literal delimiter, e.g. '%%' for '%'
+        if text[open.len()..].starts_with(open) {
+            new_line.push_str(open);
+            rest = &text[2 * open.len()..];
+            continue;
+        }
+
+        let Some((inner, length)) = find_delimited_expression(text, dialect, relaxed)? else {
+            // Not an expression, e.g. '50% of 100%': keep the first character and continue after it
+            let next = text.chars().next().map_or(1, char::len_utf8);
+            new_line.push_str(&text[..next]);
+            rest = &text[next..];
+            continue;
+        };
+
+        match expand_inner_expression(inner, flags, filters, variables)? {
+            Expansion::Value(value) => new_line.push_str(&value),
+            // Expressions that are not replaced, e.g. because of '--no-replace-unset', are kept
+            // as written, including the delimiters and the whitespace inside of them
+            Expansion::Kept(_, color) => {
+                new_line.push_str(&colorize_text(colored, text[..length].to_string(), color));
+            }
+        }
+
+        rest = &text[length..];
+    }
+
+    new_line.push_str(rest);
+    return Ok(new_line);
+}
+
+/// A braced expression that is still open at the end of a line, e.g. `${VAR:-` without the
+/// closing brace.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct OpenExpression {
+    /// The number of characters from the opening `${` to the end of the line.
+    length: usize,
+    /// The number of nested `${` inside of the expression that are not closed yet.
+    depth: usize,
+}
+
+/// Scans `line` for a braced expression that is still open at its end.
+///
+/// The line is scanned the same way as in `replace_vars_in_line`, including nested expressions
+/// and escaped dollar signs. Only `line` is scanned, the state of the previous lines is carried
+/// over in `open`.
+///
+/// # Arguments
+///
+/// * `line` - The line to scan, including its line ending.
+/// * `open` - The expression that is still open at the end of the previous line, if any.
+/// * `check_escape` - Whether two dollar signs escape a variable.
+///
+/// # Returns
+///
+/// * `Option<OpenExpression>` - The expression that is still open at the end of `line`, or
+///   `None` if all braced expressions are closed.
+///
+fn scan_open_expression(
+    line: &str,
+    open: Option<OpenExpression>,
+    check_escape: bool,
+) -> Option<OpenExpression> {
+    // '$', '{' and '}' are ASCII, so the bytes can be scanned without decoding the characters
+    let bytes = line.as_bytes();
+    let mut depth = open.map(|open| open.depth);
+    // Byte index of the '${' if the open expression starts in this line
+    let mut start: Option<usize> = None;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match (depth, bytes[i], bytes.get(i + 1)) {
+            (None, b'$', Some(b'$')) if check_escape => i += 1,
+            // ${1VAR} is not a valid variable and is kept as is
+            (None, b'$', Some(b'{')) if !bytes.get(i + 2).is_some_and(u8::is_ascii_digit) => {
+                depth = Some(0);
+                start = Some(i);
+                i += 1;
+            }
+            (Some(nested), b'$', Some(b'{')) => {
+                depth = Some(nested + 1);
+                i += 1;
+            }
+            (Some(0), b'}', _) => {
+                depth = None;
+                start = None;
+            }
+            (Some(nested), b'}', _) => depth = Some(nested - 1),
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let length = match start {
+        Some(start) => line[start..].chars().count(),
+        None => open?.length + line.chars().count(),
+    };
+    return depth.map(|depth| OpenExpression { length, depth });
+}
+
+/// Replaces the variables in `lines` and passes the replaced lines to `emit`.
+///
+/// With `--multiline-expressions`, the lines of a braced expression that spans multiple lines,
+/// e.g. with a long default value, are joined before the variables are replaced. If the
+/// expression exceeds the maximum length of the `settings` or is still open at the end of the
+/// input, the line with the opening `${` is kept as is and the following lines are replaced one
+/// at a time again.
+///
+/// # Arguments
+///
+/// * lines - An iterator over the lines, including their line endings.
+/// * flags - A reference to a Flags instance containing the flag settings for variable replacement.
+/// * filters - A reference to a Filters instance containing the variable name filters for variable replacement.
+/// * settings - A reference to a Settings instance, e.g. with the maximum length of expressions spanning lines.
+/// * variables - The variables of the current render.
+/// * emit - Receives the replaced lines.
+///
+/// # Returns
+///
+/// * Result<(), String> - A Result containing either a () on success or an error message as a String on failure.
+///
+/// # Errors
+///
+/// This function will return an error if reading a line fails, if there is an error in replacing
+/// variables, or if `emit` fails.
+pub fn replace_lines(
+    lines: impl Iterator<Item = Result<String, String>>,
+    flags: &Flags,
+    filters: &Filters,
+    settings: &Settings,
+    variables: &mut Variables,
+    emit: &mut dyn FnMut(String) -> Result<(), String>,
+) -> Result<(), String> {
+    // Lines of a braced expression that is not closed yet
+    let mut pending: Vec<String> = Vec::new();
+    // Lines to scan again, because the expression they followed was never closed
+    let mut rescan: VecDeque<String> = VecDeque::new();
+    let mut open: Option<OpenExpression> = None;
+    let check_escape = !flags.is_flag_set(Flag::NoEscape);
+    let max_expression_length = settings.max_expression_length();
+    let dialect = settings.dialect();
+    let multiline = flags.is_flag_set(Flag::MultilineExpressions) && dialect == Dialect::Shell;
+
+    let mut lines = lines.peekable();
+    let mut replace = |line: &str| -> Result<(), String> {
+        return match replace_delimited_vars(line, dialect, flags, filters, variables) {
+            Ok(out) => emit(out),
+            Err(e) => Err(format!("failed to replace variables: {e}")),
+        };
+    };
+
+    loop {
+        let line = match rescan.pop_front() {
+            Some(line) => line,
+            None => match lines.next() {
+                Some(line_res) => line_res?,
+                None => break,
+            },
+        };
+        if multiline {
+            open = scan_open_expression(&line, open, check_escape);
+        }
+        pending.push(line);
+
+        // A braced expression may span multiple lines, e.g. with a long default value.
+        // Read the next line unless the expression exceeds the maximum length.
+        let has_more = !rescan.is_empty() || lines.peek().is_some();
+        match open.take() {
+            Some(expression) if has_more && expression.length <= max_expression_length => {
+                open = Some(expression);
+            }
+            Some(_) if pending.len() > 1 => {
+                // The expression is never closed, e.g. because of a stray '${'. Keep the line
+                // with the '${' as is and scan the following lines again on their own.
+                let first = pending.remove(0);
+                replace(&first)?;
+                for line in pending.drain(..).rev() {
+                    rescan.push_front(line);
+                }
+            }
+            _ => {
+                let text = if pending.len() == 1 {
+                    pending.swap_remove(0)
+                } else {
+                    std::mem::take(&mut pending).concat()
+                };
+                replace(&text)?;
+            }
+        }
+    }
+
+    return Ok(());
+}
+
 /// Processes the input from a given Read instance and writes the result to a given Write instance.
 ///
 /// This function reads input from a Read instance and replaces any variables found in the input
 /// with their corresponding environment variable values, according to the specified Flags and Filters.
+/// If directives are enabled, the block directives like `#if` are evaluated as well.
 /// The resulting output is then written to a Write instance.
 ///
 /// # Arguments
@@ -184,6 +703,7 @@ fn replace_vars_in_line(line: &str, flags: &Flags, filters: &Filters) -> Result<
 /// * output - A Write instance to which to write the resulting output data.
 /// * flags - A reference to a Flags instance containing the flag settings for variable replacement.
 /// * filters - A reference to a Filters instance containing the variable name filters for variable replacement.
+/// * settings - A reference to a Settings instance, e.g. with the maximum length of expressions spanning lines.
 ///
 /// # Returns
 ///
@@ -191,37 +711,45 @@ fn replace_vars_in_line(line: &str, flags: &Flags, filters: &Filters) -> Result<
 ///
 /// # Errors
 ///
-/// This function will return an error if any I/O operation fails, or if there is an error in replacing variables in the input data.
+/// This function will return an error if any I/O operation fails, if there is an error in replacing variables
+/// in the input data, or if the directives are invalid.
 ///
 pub fn process_input<R: std::io::Read, W: std::io::Write>(
     input: R,
     mut output: W,
     flags: &Flags,
     filters: &Filters,
+    settings: &Settings,
 ) -> Result<(), String> {
     let reader: BufReader<R> = BufReader::new(input);
     let mut buffer = String::new();
+    let mut variables = Variables::load(settings, flags)?;
     let unbuffered_lines = flags
         .get(Flag::UnbufferedLines)
-        .map_or(false, |f| f.value.unwrap_or(false));
-
-    for line_res in read_lines(reader) {
-        let line = line_res.map_err(|e| e.to_string())?;
-        let replaced: Result<String, String> = replace_vars_in_line(&line, flags, filters);
-        match replaced {
-            Ok(out) => {
-                // If unbuffered lines mode is enabled, write each line as soon as it's processed
-                if unbuffered_lines {
-                    if let Err(e) = output.write(out.as_bytes()) {
-                        return Err(format!("failed to write to output: {e}"));
-                    }
-                    continue;
-                }
-                // If unbuffered lines mode is not enabled, append the line to the buffer
-                buffer.push_str(&out);
+        .is_some_and(|f| f.value.unwrap_or(false));
+
+    let mut emit = |out: String| -> Result<(), String> {
+        // If unbuffered lines mode is enabled, write each line as soon as it's processed
+        if unbuffered_lines {
+            if let Err(e) = output.write(out.as_bytes()) {
+                return Err(format!("failed to write to output: {e}"));
             }
-            Err(e) => return Err(format!("failed to replace variables: {e}")),
+            return Ok(());
         }
+        // If unbuffered lines mode is not enabled, append the line to the buffer
+        buffer.push_str(&out);
+        return Ok(());
+    };
+
+    let lines = read_lines(reader).map(|line| line.map_err(|e| e.to_string()));
+
+    if flags.is_flag_set(Flag::Directives) {
+        // Directives need the whole template, e.g. to find the matching '#endif'
+        let lines: Vec<String> = lines.collect::<Result<_, _>>()?;
+        let mut renderer = Renderer::new(flags, filters, settings, &mut variables);
+        renderer.render_lines(&lines, &mut emit)?;
+    } else {
+        replace_lines(lines, flags, filters, settings, &mut variables, &mut emit)?;
     }
 
     // If unbuffered lines mode is not enabled, write the entire buffer to the output file at once
@@ -245,7 +773,9 @@ mod tests {
     use super::*;
 
     use crate::filters::Filter;
+    use crate::settings::Setting;
     use colored::{Color, Colorize};
+    use std::env;
     use std::io::Cursor;
 
     // A dummy implementation of a writer that always fails to write and flush
@@ -273,10 +803,7 @@ mod tests {
         fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
             if self.fail_on_write {
                 self.fail_on_write = false;
-                Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Simulated write error",
-                ))
+                Err(std::io::Error::other("Simulated write error"))
             } else {
                 self.writer.write(buf)
             }
@@ -285,10 +812,7 @@ mod tests {
         fn flush(&mut self) -> std::io::Result<()> {
             if self.fail_on_flush {
                 self.fail_on_flush = false;
-                Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Simulated flush error",
-                ))
+                Err(std::io::Error::other("Simulated flush error"))
             } else {
                 self.writer.flush()
             }
@@ -299,7 +823,7 @@ mod tests {
     fn test_read_lines_unix_endings() {
         // Test Unix line endings
         let input = "Hello $WORLD!\nHello $WORLD!\nHello $WORLD!\n";
-        let expected = vec!["Hello $WORLD!\n", "Hello $WORLD!\n", "Hello $WORLD!\n"];
+        let expected = ["Hello $WORLD!\n", "Hello $WORLD!\n", "Hello $WORLD!\n"];
         let lines = read_lines(input.as_bytes());
         for (i, line_result) in lines.enumerate() {
             let line = line_result.unwrap();
@@ -311,7 +835,7 @@ mod tests {
     fn test_read_lines_windows_endings() {
         // Test Windows line endings
         let input = "Hello $WORLD!\r\nHello $WORLD!\r\nHello $WORLD!\r\n";
-        let expected = vec![
+        let expected = [
             "Hello $WORLD!\r\n",
             "Hello $WORLD!\r\n",
             "Hello $WORLD!\r\n",
@@ -327,7 +851,7 @@ mod tests {
     fn test_read_lines_emojis() {
         // Test Emojis
         let input = "Hello 😃 World!\nHello 😃 World!\nHello 😃 World!\n";
-        let expected = vec![
+        let expected = [
             "Hello 😃 World!\n",
             "Hello 😃 World!\n",
             "Hello 😃 World!\n",
@@ -357,7 +881,7 @@ mod tests {
 
         // Test character after dollar sign is invalid escaped
         let line = "this is a test line with invalid character after dollar sign $$1";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "this is a test line with invalid character after dollar sign $$1"
@@ -365,7 +889,7 @@ mod tests {
 
         // Test character after dollar sign is invalid
         let line = "this is a test line with invalid character after dollar sign $1";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "this is a test line with invalid character after dollar sign $1"
@@ -373,7 +897,7 @@ mod tests {
 
         // Test variable with double dollar sign at the end
         let line = "this is a test line with two dollar sign at the end of line $$";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "this is a test line with two dollar sign at the end of line $$"
@@ -381,27 +905,27 @@ mod tests {
 
         // Test dollar sign at the end
         let line = "This is a dollar sign at the end: $";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a dollar sign at the end: $");
 
         // Test basic variable replacement
         let line = "This is a $VAR.";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a value.");
 
         // Test two variables in the same line
         let line = "$VAR and $ANOTHER_VAR";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "value and another_value");
 
         // Test escaped variable
         let line = "This is an escaped variable: $$VAR";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is an escaped variable: $VAR");
 
         // Test escaped variable at the end of the line
         let line = "This is an escaped variable at the end: $$VAR$";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "This is an escaped variable at the end: $VAR$"
@@ -409,12 +933,12 @@ mod tests {
 
         // Test invalid variable
         let line = "This is an invalid variable: $1VAR";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is an invalid variable: $1VAR");
 
         // Test incomplete brace variable
         let line = "This is an incomplete brace variable: ${VAR";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "This is an incomplete brace variable: ${VAR"
@@ -422,7 +946,7 @@ mod tests {
 
         // Test variable with default value
         let line = "This is a variable with a default value: ${UNSET_VAR:-default}";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "This is a variable with a default value: default"
@@ -430,7 +954,7 @@ mod tests {
 
         // Test variable with default but variable found
         let line = "This is a variable with a default value: ${VAR:-default}";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "This is a variable with a default value: value"
@@ -438,7 +962,7 @@ mod tests {
 
         // Test variable with substring extraction
         let line = "This is a variable with substring extraction: ${VAR:1:3}";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "This is a variable with substring extraction: alu"
@@ -446,7 +970,7 @@ mod tests {
 
         // Test invalid variables like ${1VAR}
         let line = "this is a test line with invalid variable ${1VAR}";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "this is a test line with invalid variable ${1VAR}"
@@ -454,25 +978,33 @@ mod tests {
 
         // Test invalid variables like ${1VAR:-DEFAULT}
         let line = "this is a test line with invalid variable ${1VAR:-DEFAULT}";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "this is a test line with invalid variable ${1VAR:-DEFAULT}"
         );
 
+        // Test length of braced var
+        let line = "This is the length of a braced variable: ${#VAR}";
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
+        assert_eq!(
+            result.unwrap(),
+            "This is the length of a braced variable: 5"
+        );
+
         // Test braced var to upper
         let line = "This is a braced variable to upper: ${VAR^^}";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a braced variable to upper: VALUE");
 
         // Test braced var to lower
         let line = "This is a braced variable to lower: ${VAR,,}";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a braced variable to lower: value");
 
         // Test braced var first char to upper
         let line = "This is a braced variable first char to upper: ${VAR^}";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "This is a braced variable first char to upper: Value"
@@ -480,13 +1012,459 @@ mod tests {
 
         // Test braced var first char to lower
         let line = "This is a braced variable first char to lower: ${VAR,}";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "This is a braced variable first char to lower: value"
         );
     }
 
+    #[test]
+    fn test_replace_vars_in_line_nested_expansion() {
+        let mut flags = Flags::default();
+        let filters = Filters::default();
+        env::set_var("NESTED_LINE_GLOBAL", "global");
+
+        let line = "url: ${NESTED_LINE_SERVICE:-${NESTED_LINE_GLOBAL:-literal}}/path";
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "url: global/path");
+
+        let line = "url: ${NESTED_LINE_SERVICE:-${NESTED_LINE_UNSET:-literal}}}";
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "url: literal}");
+
+        // An unterminated nested expression is kept as is
+        let line = "url: ${NESTED_LINE_SERVICE:-${NESTED_LINE_GLOBAL}";
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
+        assert_eq!(
+            result.unwrap(),
+            "url: ${NESTED_LINE_SERVICE:-${NESTED_LINE_GLOBAL}"
+        );
+
+        // Only the outer expression is colored
+        colored::control::set_override(true);
+        flags
+            .set(Flag::Color, "--color", true)
+            .expect("Failed to set Color flag");
+        let line = "${NESTED_LINE_SERVICE:-${NESTED_LINE_GLOBAL}}";
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "global".color(Color::Yellow).to_string());
+    }
+
+    #[test]
+    fn test_replace_vars_in_line_nesting_depth() {
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let nested = |depth: usize| -> String {
+            return format!(
+                "{}x{}",
+                "${NESTING_DEPTH_UNSET:-".repeat(depth + 1),
+                "}".repeat(depth + 1)
+            );
+        };
+
+        let line = nested(MAX_NESTING_DEPTH);
+        let result = replace_vars_in_line(&line, &flags, &filters, &mut Variables::default());
+        assert_eq!(result, Ok("x".to_string()));
+
+        // Deeper expressions fail instead of overflowing the stack
+        for depth in [MAX_NESTING_DEPTH + 1, 10_000] {
+            let line = nested(depth);
+            let result = replace_vars_in_line(&line, &flags, &filters, &mut Variables::default());
+            assert_eq!(
+                result,
+                Err(format!(
+                    "Expression is nested more than {MAX_NESTING_DEPTH} levels deep"
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_replace_vars_in_line_arithmetic() {
+        let flags = Flags::default();
+        let filters = Filters::default();
+        env::set_var("ARITHMETIC_LINE_PORT", "8080");
+
+        let line = "port: $((ARITHMETIC_LINE_PORT + 1)), workers: $(( (2 + 1) * 2 ))";
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "port: 8081, workers: 6");
+
+        let line = "${ARITHMETIC_LINE_UNSET:-$((ARITHMETIC_LINE_PORT - 80))}";
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "8000");
+
+        // Command substitutions and unterminated expressions are kept as is
+        let line = "$(date) $((1 + 2)";
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "$(date) $((1 + 2)");
+
+        let line = "$((1 / 0))";
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
+        assert_eq!(result, Err("\"$((1 / 0))\" - Division by zero".to_string()));
+    }
+
+    #[test]
+    fn test_is_delimited_expression() {
+        assert!(is_delimited_expression("VAR", false));
+        assert!(is_delimited_expression("VAR:-default", false));
+        assert!(is_delimited_expression("#VAR", false));
+        assert!(is_delimited_expression("_V1 | upper", false));
+        assert!(!is_delimited_expression(" of 100", false));
+        assert!(!is_delimited_expression("example.com and admin", false));
+        assert!(!is_delimited_expression("alice and ", false));
+        assert!(!is_delimited_expression("OFF and 10", false));
+        assert!(!is_delimited_expression("range .Items", false));
+        assert!(!is_delimited_expression("alice - bob", false));
+        assert!(!is_delimited_expression("a |b", false));
+        assert!(!is_delimited_expression("1VAR", false));
+        assert!(!is_delimited_expression("", false));
+        assert!(!is_delimited_expression("db.host:-x", false));
+        assert!(is_delimited_expression("db.host:-x", true));
+    }
+
+    #[test]
+    fn test_replace_delimited_vars() {
+        env::set_var("DIALECT_HOST", "Example.com");
+        env::remove_var("DIALECT_UNSET");
+        let filters = Filters::default();
+        let flags = Flags::default();
+
+        let cases = [
+            (
+                Dialect::Mustache,
+                "{{DIALECT_HOST}} {{ DIALECT_HOST | lower }}",
+                "Example.com example.com",
+            ),
+            (
+                Dialect::Mustache,
+                "{{DIALECT_UNSET:-$DIALECT_HOST}} { {x}} {{",
+                "Example.com { {x}} {{",
+            ),
+            (
+                Dialect::Autoconf,
+                "host=@DIALECT_HOST@ mail=admin@example.com",
+                "host=Example.com mail=admin@example.com",
+            ),
+            (
+                Dialect::Autoconf,
+                "a@b.c d@e.f @#DIALECT_HOST@",
+                "a@b.c d@e.f 11",
+            ),
+            (
+                Dialect::Batch,
+                "50% of %DIALECT_HOST,,% is 100%",
+                "50% of example.com is 100%",
+            ),
+            (
+                Dialect::Batch,
+                "$DIALECT_HOST %%DIALECT_HOST% 100%%",
+                "$DIALECT_HOST %DIALECT_HOST% 100%",
+            ),
+            // Prose with two delimiters is kept as is
+            (
+                Dialect::Autoconf,
+                "ping @alice and @bob now @DIALECT_HOST@",
+                "ping @alice and @bob now Example.com",
+            ),
+            (
+                Dialect::Batch,
+                "save 5%OFF and 10% more",
+                "save 5%OFF and 10% more",
+            ),
+            (
+                Dialect::Mustache,
+                "{{ range .Items }}{{ DIALECT_HOST }} {{ .Name }}",
+                "{{ range .Items }}Example.com {{ .Name }}",
+            ),
+        ];
+        for (dialect, line, expected) in cases {
+            assert_eq!(
+                replace_delimited_vars(line, dialect, &flags, &filters, &mut Variables::default()),
+                Ok(expected.to_string()),
+                "{line}"
+            );
+        }
+
+        // Not replaced variables keep the delimiters of the dialect
+        let mut flags = Flags::default();
+        flags
+            .set(Flag::NoReplaceUnset, "--no-replace-unset", true)
+            .unwrap();
+        assert_eq!(
+            replace_delimited_vars(
+                "@DIALECT_UNSET@ @DIALECT_HOST@",
+                Dialect::Autoconf,
+                &flags,
+                &filters,
+                &mut Variables::default()
+            ),
+            Ok("@DIALECT_UNSET@ Example.com".to_string())
+        );
+
+        // The expression is kept as written, and values that look like expressions are not changed
+        env::set_var("DIALECT_LITERAL", "${DIALECT_UNSET}");
+        assert_eq!(
+            replace_delimited_vars(
+                "{{ DIALECT_UNSET }} {{DIALECT_UNSET | upper}} {{DIALECT_LITERAL}}",
+                Dialect::Mustache,
+                &flags,
+                &filters,
+                &mut Variables::default()
+            ),
+            Ok("{{ DIALECT_UNSET }} {{DIALECT_UNSET | upper}} ${DIALECT_UNSET}".to_string())
+        );
+
+        let mut filters = Filters::default();
+        filters
+            .add(Filter::Prefix, "--prefix", Some("APP_"), &mut [].iter())
+            .unwrap();
+        assert_eq!(
+            replace_delimited_vars(
+                "{{DIALECT_HOST}}",
+                Dialect::Mustache,
+                &Flags::default(),
+                &filters,
+                &mut Variables::default()
+            ),
+            Ok("{{DIALECT_HOST}}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_replace_delimited_vars_nested() {
+        env::set_var("DIALECT_NESTED_HOST", "example.com");
+        env::remove_var("DIALECT_NESTED_UNSET");
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let mut variables = Variables::default();
+
+        for (dialect, line, expected) in [
+            (
+                Dialect::Mustache,
+                "{{DIALECT_NESTED_UNSET:-${DIALECT_NESTED_HOST}}}",
+                "example.com",
+            ),
+            (
+                Dialect::Mustache,
+                "{{ DIALECT_NESTED_UNSET:-${DIALECT_NESTED_UNSET:-{x}} }}",
+                "{x}",
+            ),
+            (
+                Dialect::Autoconf,
+                "@DIALECT_NESTED_UNSET:-${DIALECT_NESTED_HOST}@",
+                "example.com",
+            ),
+            // A doubled delimiter is a literal delimiter
+            (
+                Dialect::Mustache,
+                "{{{{DIALECT_NESTED_HOST}} {{{DIALECT_NESTED_HOST}}}",
+                "{{DIALECT_NESTED_HOST}} {example.com}",
+            ),
+            (
+                Dialect::Autoconf,
+                "@@DIALECT_NESTED_HOST@@ @DIALECT_NESTED_HOST@@@",
+                "@DIALECT_NESTED_HOST@ example.com@",
+            ),
+            // Adjacent expressions and text with whitespace after an expression
+            (
+                Dialect::Batch,
+                "%DIALECT_NESTED_HOST%%DIALECT_NESTED_HOST% %DIALECT_NESTED_HOST%% of 100%",
+                "example.comexample.com example.com% of 100%",
+            ),
+            (
+                Dialect::Autoconf,
+                "@DIALECT_NESTED_HOST@/lib/@DIALECT_NESTED_HOST@",
+                "example.com/lib/example.com",
+            ),
+        ] {
+            assert_eq!(
+                replace_delimited_vars(line, dialect, &flags, &filters, &mut variables),
+                Ok(expected.to_string()),
+                "{line}"
+            );
+        }
+
+        for (dialect, line, expected) in [
+            (
+                Dialect::Mustache,
+                "{{DIALECT_NESTED_UNSET:-{{DIALECT_NESTED_HOST}}}}",
+                "\"{{DIALECT_NESTED_UNSET:-{{DIALECT_NESTED_HOST}}}}\" - Nested '{{' are not supported, use '${...}' for nested expressions",
+            ),
+            (
+                Dialect::Batch,
+                "%DIALECT_NESTED_HOST%%.com%",
+                "\"%DIALECT_NESTED_HOST%%.com%\" - Ambiguous expression, operators cannot contain the delimiter '%'",
+            ),
+            (
+                Dialect::Autoconf,
+                "@DIALECT_NESTED_HOST@Q@",
+                "\"@DIALECT_NESTED_HOST@Q@\" - Ambiguous expression, operators cannot contain the delimiter '@'",
+            ),
+        ] {
+            assert_eq!(
+                replace_delimited_vars(line, dialect, &flags, &filters, &mut variables),
+                Err(expected.to_string()),
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_scan_open_expression() {
+        let open = |length, depth| Some(OpenExpression { length, depth });
+        assert_eq!(scan_open_expression("no expression", None, true), None);
+        assert_eq!(
+            scan_open_expression("${VAR} ${VAR:-", None, true),
+            open(7, 0)
+        );
+        assert_eq!(
+            scan_open_expression("${VAR:-${OTHER}\n", None, true),
+            open(16, 0)
+        );
+        assert_eq!(
+            scan_open_expression("${VAR:-${OTHER:-x}}", None, true),
+            None
+        );
+        assert_eq!(scan_open_expression("${1VAR:-", None, true), None);
+        assert_eq!(scan_open_expression("$${VAR:-", None, true), None);
+        assert_eq!(scan_open_expression("$${VAR:-", None, false), open(7, 0));
+
+        // The state of the previous lines is carried over
+        let first = scan_open_expression("${VAR:-${OTHER:-\n", None, true);
+        assert_eq!(first, open(17, 1));
+        let second = scan_open_expression("x}\n", first, true);
+        assert_eq!(second, open(20, 0));
+        assert_eq!(scan_open_expression("}\n", second, true), None);
+        assert_eq!(scan_open_expression("}} ${A:-\n", first, true), open(6, 0));
+    }
+
+    #[test]
+    fn test_process_input_multiline_expression() {
+        let input =
+            "config: ${MULTILINE_UNSET:-{\n  \"key\": \"value\"\n}}\nnext: $MULTILINE_VAR\n";
+        let filters = Filters::default();
+        env::set_var("MULTILINE_VAR", "value");
+
+        // Without '--multiline-expressions', every line is replaced on its own
+        let mut output = Vec::new();
+        process_input(
+            Cursor::new(input.as_bytes()),
+            &mut output,
+            &Flags::default(),
+            &filters,
+            &Settings::default(),
+        )
+        .expect("Failed to process input");
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "config: ${MULTILINE_UNSET:-{\n  \"key\": \"value\"\n}}\nnext: value\n"
+        );
+
+        let mut flags = Flags::default();
+        flags
+            .set(Flag::MultilineExpressions, "--multiline-expressions", true)
+            .unwrap();
+        let mut output = Vec::new();
+        process_input(
+            Cursor::new(input.as_bytes()),
+            &mut output,
+            &flags,
+            &filters,
+            &Settings::default(),
+        )
+        .expect("Failed to process input");
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "config: {\n  \"key\": \"value\"\n}\nnext: value\n"
+        );
+
+        // An expression exceeding the maximum length is kept as is
+        let mut settings = Settings::default();
+        settings
+            .set(
+                Setting::MaxExpressionLength,
+                "--max-expression-length",
+                Some("10"),
+                &mut [].iter(),
+            )
+            .expect("Failed to set maximum expression length");
+        let mut output = Vec::new();
+        process_input(
+            Cursor::new(input.as_bytes()),
+            &mut output,
+            &flags,
+            &filters,
+            &settings,
+        )
+        .expect("Failed to process input");
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "config: ${MULTILINE_UNSET:-{\n  \"key\": \"value\"\n}}\nnext: value\n"
+        );
+
+        // An expression that is still open at the end of the input is kept as is, but the
+        // following lines are replaced
+        let input = "start ${MULTILINE_VAR:-\n$MULTILINE_VAR";
+        let mut output = Vec::new();
+        process_input(
+            Cursor::new(input.as_bytes()),
+            &mut output,
+            &flags,
+            &filters,
+            &Settings::default(),
+        )
+        .expect("Failed to process input");
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "start ${MULTILINE_VAR:-\nvalue"
+        );
+    }
+
+    #[test]
+    fn test_process_input_stray_open_expression() {
+        let input = "price: ${oops\nhost: $STRAY_HOST\nport: ${STRAY_PORT}\n${STRAY_HOST:-\nx}\n";
+        env::set_var("STRAY_HOST", "h");
+        env::set_var("STRAY_PORT", "1");
+        let mut flags = Flags::default();
+        flags
+            .set(Flag::MultilineExpressions, "--multiline-expressions", true)
+            .unwrap();
+
+        // Only the stray '${' is kept as is, an expression exceeding the maximum length as well
+        for (max_expression_length, expected) in [
+            ("4096", "price: ${oops\nhost: h\nport: 1\nh\n"),
+            (
+                "10",
+                "price: ${oops\nhost: h\nport: 1\n${STRAY_HOST:-\nx}\n",
+            ),
+        ] {
+            let mut settings = Settings::default();
+            settings
+                .set(
+                    Setting::MaxExpressionLength,
+                    "--max-expression-length",
+                    Some(max_expression_length),
+                    &mut [].iter(),
+                )
+                .expect("Failed to set maximum expression length");
+            let mut output = Vec::new();
+            process_input(
+                Cursor::new(input.as_bytes()),
+                &mut output,
+                &flags,
+                &Filters::default(),
+                &settings,
+            )
+            .expect("Failed to process input");
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                expected,
+                "--max-expression-length {max_expression_length}"
+            );
+        }
+    }
+
     #[test]
     fn test_process_input_basic() {
         let input = "This is a $VAR.";
@@ -495,11 +1473,189 @@ mod tests {
         let filters = Filters::default();
         env::set_var("VAR", "value");
 
-        process_input(Cursor::new(input.as_bytes()), &mut output, &flags, &filters)
-            .expect("Failed to process input");
+        process_input(
+            Cursor::new(input.as_bytes()),
+            &mut output,
+            &flags,
+            &filters,
+            &Settings::default(),
+        )
+        .expect("Failed to process input");
         assert_eq!(String::from_utf8(output).unwrap(), "This is a value.");
     }
 
+    #[test]
+    fn test_process_input_directives() {
+        let input =
+            "${DIRECTIVES_TLS:=on}\n#if DIRECTIVES_TLS == on\ntls: $DIRECTIVES_TLS\n#endif\n";
+        let mut flags = Flags::default();
+        let filters = Filters::default();
+        env::remove_var("DIRECTIVES_TLS");
+
+        // Without the flag, directives are regular text
+        let mut output = Vec::new();
+        process_input(
+            Cursor::new(input.as_bytes()),
+            &mut output,
+            &flags,
+            &filters,
+            &Settings::default(),
+        )
+        .expect("Failed to process input");
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "on\n#if DIRECTIVES_TLS == on\ntls: on\n#endif\n"
+        );
+
+        // Values assigned before a condition are visible to it
+        flags.set(Flag::Directives, "--directives", true).unwrap();
+        let mut output = Vec::new();
+        process_input(
+            Cursor::new(input.as_bytes()),
+            &mut output,
+            &flags,
+            &filters,
+            &Settings::default(),
+        )
+        .expect("Failed to process input");
+        assert_eq!(String::from_utf8(output).unwrap(), "on\ntls: on\n");
+
+        let result = process_input(
+            Cursor::new("#if DIRECTIVES_TLS\n".as_bytes()),
+            &mut Vec::new(),
+            &flags,
+            &filters,
+            &Settings::default(),
+        );
+        assert_eq!(
+            result,
+            Err(
+                "failed to process directives: line 1: '#if' is not closed with '#endif'"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_process_input_dialect() {
+        let input = "#!/bin/sh\nexec \"$@\" --port=@DIALECT_PORT:=8080@ ${\n@DIALECT_PORT@\n";
+        let mut settings = Settings::default();
+        settings
+            .set(
+                Setting::Dialect,
+                "--dialect",
+                Some("autoconf"),
+                &mut [].iter(),
+            )
+            .unwrap();
+        env::remove_var("DIALECT_PORT");
+
+        let mut output = Vec::new();
+        process_input(
+            Cursor::new(input.as_bytes()),
+            &mut output,
+            &Flags::default(),
+            &Filters::default(),
+            &settings,
+        )
+        .expect("Failed to process input");
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "#!/bin/sh\nexec \"$@\" --port=8080 ${\n8080\n"
+        );
+    }
+
+    #[test]
+    fn test_process_input_env_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let env_file = dir.path().join(".env");
+        std::fs::write(
+            &env_file,
+            "export ENV_FILE_HOST=db
+ENV_FILE_MESSAGE=\"it's\\n$HOME\"\n",
+        )
+        .unwrap();
+        env::set_var("ENV_FILE_HOST", "localhost");
+        let input = "${ENV_FILE_HOST}:${ENV_FILE_MESSAGE}";
+
+        let mut settings = Settings::default();
+        settings
+            .set(
+                Setting::EnvFile,
+                "--env-file",
+                env_file.to_str(),
+                &mut [].iter(),
+            )
+            .unwrap();
+
+        let mut output = Vec::new();
+        process_input(
+            Cursor::new(input.as_bytes()),
+            &mut output,
+            &Flags::default(),
+            &Filters::default(),
+            &settings,
+        )
+        .expect("Failed to process input");
+        assert_eq!(String::from_utf8(output).unwrap(), "db:it's\n$HOME");
+
+        let mut flags = Flags::default();
+        flags
+            .set(Flag::PreferEnvironment, "--prefer-env", true)
+            .unwrap();
+        let mut output = Vec::new();
+        process_input(
+            Cursor::new(input.as_bytes()),
+            &mut output,
+            &flags,
+            &Filters::default(),
+            &settings,
+        )
+        .expect("Failed to process input");
+        assert_eq!(String::from_utf8(output).unwrap(), "localhost:it's\n$HOME");
+    }
+
+    #[test]
+    fn test_process_input_assign_default_value() {
+        let input = "${UNSET_ASSIGNED_VAR:=default} and $UNSET_ASSIGNED_VAR\n${UNSET_ASSIGNED_VAR}";
+        let mut output = Vec::new();
+        let flags = Flags::default();
+        let filters = Filters::default();
+
+        process_input(
+            Cursor::new(input.as_bytes()),
+            &mut output,
+            &flags,
+            &filters,
+            &Settings::default(),
+        )
+        .expect("Failed to process input");
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "default and default\ndefault"
+        );
+    }
+
+    #[test]
+    fn test_process_input_required_value_error() {
+        let input = "first line\nrequired: ${UNSET_REQUIRED_VAR:?please set it}\n";
+        let mut output = Vec::new();
+        let flags = Flags::default();
+        let filters = Filters::default();
+
+        let result = process_input(
+            Cursor::new(input.as_bytes()),
+            &mut output,
+            &flags,
+            &filters,
+            &Settings::default(),
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            "failed to replace variables: environment variable 'UNSET_REQUIRED_VAR': please set it"
+        );
+    }
+
     #[test]
     fn test_process_input_error() {
         let input = "This is a $VAR.";
@@ -514,6 +1670,7 @@ mod tests {
             &mut failing_writer,
             &flags,
             &filters,
+            &Settings::default(),
         );
         assert!(result.is_err());
 
@@ -521,7 +1678,7 @@ mod tests {
         let input = Cursor::new("${HelloWorld$-}");
         let output = Cursor::new(Vec::new());
 
-        let result = process_input(input, output, &flags, &filters);
+        let result = process_input(input, output, &flags, &filters, &Settings::default());
         assert!(result.is_err());
     }
 
@@ -537,7 +1694,13 @@ mod tests {
         env::set_var("VAR1", "value1");
         env::set_var("VAR2", "value2");
 
-        let result = process_input(Cursor::new(input.as_bytes()), &mut output, &flags, &filters);
+        let result = process_input(
+            Cursor::new(input.as_bytes()),
+            &mut output,
+            &flags,
+            &filters,
+            &Settings::default(),
+        );
         assert!(result.is_ok());
 
         // Test unbuffered_lines write error
@@ -548,6 +1711,7 @@ mod tests {
             &mut failing_writer,
             &flags,
             &filters,
+            &Settings::default(),
         );
         assert!(result.is_err());
         assert_eq!(
@@ -571,6 +1735,7 @@ mod tests {
             &mut failing_writer,
             &flags,
             &filters,
+            &Settings::default(),
         );
         assert!(result.is_err());
         assert_eq!(
@@ -594,6 +1759,7 @@ mod tests {
             &mut failing_writer,
             &flags,
             &filters,
+            &Settings::default(),
         );
         assert!(result.is_err());
         assert_eq!(
@@ -610,7 +1776,7 @@ mod tests {
         env::set_var("VAR", "value");
 
         // Test default behavior
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a value.");
 
         // Test unbuffered_lines
@@ -619,7 +1785,7 @@ mod tests {
             .set(Flag::UnbufferedLines, "--unbuffered-lines", true)
             .expect("Failed to set unbuffered lines flag");
 
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a value.");
 
         // Test --no-replace-empty - simple var
@@ -629,7 +1795,7 @@ mod tests {
         flags
             .set(Flag::NoReplaceEmpty, "--no-replace-empty", true)
             .expect("Failed to set no replace empty flag");
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This following var is empty: $EMPTY_VAR!");
 
         // Test --no-replace-empty - brace var
@@ -639,7 +1805,7 @@ mod tests {
         flags
             .set(Flag::NoReplaceEmpty, "--no-replace-empty", true)
             .expect("Failed to set no replace empty flag");
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "This following var is empty: ${EMPTY_VAR}!"
@@ -651,7 +1817,7 @@ mod tests {
         flags
             .set(Flag::NoReplaceUnset, "--no-replace-unset", true)
             .expect("Failed to set no replace unset flag");
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This following var is unset: $UNSET_VAR!");
 
         // Test --no-replace-unset - brace var
@@ -660,7 +1826,7 @@ mod tests {
         flags
             .set(Flag::NoReplaceUnset, "--no-replace-unset", true)
             .expect("Failed to set no replace unset flag");
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "This following var is unset: ${UNSET_VAR}!"
@@ -676,7 +1842,7 @@ mod tests {
         flags
             .set(Flag::NoReplaceUnset, "--no-replace-unset", true)
             .expect("Failed to set no replace unset flag");
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             "This following var is unset: ${UNSET_VAR}! This following var is empty: $EMPTY_VAR!"
@@ -688,7 +1854,7 @@ mod tests {
         flags
             .set(Flag::FailOnUnset, "--fail-on-unset", true)
             .expect("Failed to set fail on unset flag");
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err(),
@@ -702,7 +1868,7 @@ mod tests {
         flags
             .set(Flag::FailOnEmpty, "--fail-on-empty", true)
             .expect("Failed to set fail on empty flag");
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err(),
@@ -720,7 +1886,7 @@ mod tests {
         flags
             .set(Flag::FailOnEmpty, "--fail-on-empty", true)
             .expect("Failed to set fail on empty flag");
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err(),
@@ -735,29 +1901,72 @@ mod tests {
 
         // Test escaping
         let line = "This is a $$ESCAPED_VAR.";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a $ESCAPED_VAR.");
 
         // Test escaping - brace var
         let line = "This is a $${ESCAPED_VAR}.";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a ${ESCAPED_VAR}.");
 
         // Test escaping - simple var
         let line = "This fi$$h should not escape!";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This fi$h should not escape!");
 
         let line = "This fi$$$$h should not escape!";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This fi$$h should not escape!");
 
         // Test escaping - simple var
         let line = "This pa$$$$ word should not escape!";
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This pa$$ word should not escape!");
     }
 
+    #[test]
+    fn test_replace_vars_in_line_relaxed_names() {
+        env::set_var("RELAXED_LINE_HOST", "db.example.com");
+        let mut flags = Flags::default();
+        flags
+            .set(Flag::RelaxedNames, "--relaxed-names", true)
+            .expect("Failed to set RelaxedNames flag");
+
+        // Simple variables are looked up like braced variables, but cannot contain dots
+        let line = "${relaxed_line.host} $relaxed_line_host $RELAXED_LINE_HOST.";
+        let result =
+            replace_vars_in_line(line, &flags, &Filters::default(), &mut Variables::default());
+        assert_eq!(
+            result.unwrap(),
+            "db.example.com db.example.com db.example.com."
+        );
+
+        // Filters and errors use the name as written
+        let mut filters = Filters::default();
+        filters
+            .add(Filter::Prefix, "--prefix", Some("RELAXED"), &mut [].iter())
+            .expect("Failed to set prefix filter");
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
+        assert_eq!(
+            result.unwrap(),
+            "${relaxed_line.host} $relaxed_line_host db.example.com."
+        );
+
+        flags
+            .set(Flag::FailOnUnset, "--fail-on-unset", true)
+            .expect("Failed to set FailOnUnset flag");
+        let result = replace_vars_in_line(
+            "$relaxed_line_unset",
+            &flags,
+            &Filters::default(),
+            &mut Variables::default(),
+        );
+        assert_eq!(
+            result,
+            Err("environment variable 'relaxed_line_unset' is not set".to_string())
+        );
+    }
+
     #[test]
     fn test_replace_vars_in_line_filters() {
         // Test prefixes - empty brace variable
@@ -766,7 +1975,8 @@ mod tests {
         filters
             .add(Filter::Prefix, "--prefix", Some("PREFIX"), &mut [].iter())
             .expect("Failed to set prefix filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a .");
 
         // Test prefixes - empty simple variable
@@ -775,7 +1985,8 @@ mod tests {
         filters
             .add(Filter::Prefix, "--prefix", Some("PREFIX"), &mut [].iter())
             .expect("Failed to set prefix filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a .");
 
         // Test prefixes - found brace variable
@@ -785,7 +1996,8 @@ mod tests {
         filters
             .add(Filter::Prefix, "--prefix", Some("PREFIX"), &mut [].iter())
             .expect("Failed to set prefix filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a \"prefix\".");
 
         // Test prefixes - found simple variable
@@ -795,7 +2007,8 @@ mod tests {
         filters
             .add(Filter::Prefix, "--prefix", Some("PREFIX"), &mut [].iter())
             .expect("Failed to set prefix filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a \"prefix\".");
 
         // Test prefixes - multiple prefixes
@@ -809,7 +2022,8 @@ mod tests {
         filters
             .add(Filter::Prefix, "--prefix", Some("PFX_VAR"), &mut [].iter())
             .expect("Failed to set prefix filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a \"prefix\"\n Her anoter \"pfx\". This has var has no prefix: \"${NOT_FOUND}\".");
 
         // Test suffixes - empty brace variable
@@ -818,7 +2032,8 @@ mod tests {
         filters
             .add(Filter::Suffix, "--suffix", Some("SUFFIX"), &mut [].iter())
             .expect("Failed to set suffix filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a .");
 
         // Test suffixes - empty simple variable
@@ -827,7 +2042,8 @@ mod tests {
         filters
             .add(Filter::Suffix, "--suffix", Some("SUFFIX"), &mut [].iter())
             .expect("Failed to set suffix filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a .");
 
         // Test suffixes - found brace variable
@@ -837,7 +2053,8 @@ mod tests {
         filters
             .add(Filter::Suffix, "--suffix", Some("SUFFIX"), &mut [].iter())
             .expect("Failed to set suffix filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a \"suffix\".");
 
         // Test suffixes - found simple variable
@@ -847,7 +2064,8 @@ mod tests {
         filters
             .add(Filter::Suffix, "--suffix", Some("SUFFIX"), &mut [].iter())
             .expect("Failed to set suffix filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a \"suffix\".");
 
         // Test suffixes - multiple suffixes
@@ -866,7 +2084,8 @@ mod tests {
         filters
             .add(Filter::Prefix, "--suffix", Some("VAR_SFX"), &mut [].iter())
             .expect("Failed to set suffix filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a \"suffix\"\n Her anoter \"sfx\". This has var has no suffix: \"${NOT_FOUND}\".");
 
         // Test prefixes and suffixes - found brace variable
@@ -879,7 +2098,8 @@ mod tests {
         filters
             .add(Filter::Suffix, "--suffix", Some("SUFFIX"), &mut [].iter())
             .expect("Failed to set suffix filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a \"prefix_suffix\".");
 
         // Test prefixes and suffixes - found simple variable
@@ -892,13 +2112,19 @@ mod tests {
         filters
             .add(Filter::Suffix, "--suffix", Some("SUFFIX"), &mut [].iter())
             .expect("Failed to set suffix filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a \"prefix_suffix\".");
 
         // Test variables
         env::set_var("VAR", "value");
         let line = "This is a \"$VAR\".";
-        let result = replace_vars_in_line(line, &Flags::default(), &Filters::default());
+        let result = replace_vars_in_line(
+            line,
+            &Flags::default(),
+            &Filters::default(),
+            &mut Variables::default(),
+        );
         assert_eq!(result.unwrap(), "This is a \"value\".");
 
         // Test variables - empty variable
@@ -908,7 +2134,8 @@ mod tests {
         filters
             .add(Filter::Suffix, "--variable", Some("VAR"), &mut [].iter())
             .expect("Failed to set variable filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a \"\".");
 
         // Test variables - empty variable --no-replace
@@ -918,7 +2145,8 @@ mod tests {
         filters
             .add(Filter::Variable, "--variable", Some("VAR"), &mut [].iter())
             .expect("Failed to set variable filter");
-        let result = replace_vars_in_line(line, &Flags::default(), &filters);
+        let result =
+            replace_vars_in_line(line, &Flags::default(), &filters, &mut Variables::default());
         assert_eq!(result.unwrap(), "This is a \"$SPECIAL_VAR\".");
     }
 
@@ -941,7 +2169,7 @@ mod tests {
         let f = flags.set(Flag::Color, "-c", true);
         assert!(f.is_ok());
 
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             format!("This is a \"{}\".", "val".color(Color::Blue))
@@ -967,7 +2195,7 @@ mod tests {
         let f = flags.set(Flag::Color, "-c", true);
         assert!(f.is_ok());
 
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         assert_eq!(
             result.unwrap(),
             format!("This is a \"{}\".", "value".color(Color::Red))
@@ -992,7 +2220,7 @@ mod tests {
         let f = flags.set(Flag::Color, "-c", true);
         assert!(f.is_ok());
 
-        let result = replace_vars_in_line(line, &flags, &filters);
+        let result = replace_vars_in_line(line, &flags, &filters, &mut Variables::default());
         println!("{}", result.clone().unwrap());
         assert_eq!(
             result.unwrap(),
@@ -6,6 +6,7 @@
 /// * `MissingValue`: Indicates that a required value is missing for a given flag.
 /// * `ConflictingFlags`: Indicates that two or more conflicting flags were specified.
 /// * `DuplicateFlag`: Indicates that a flag was specified multiple times.
+/// * `InvalidValue`: Indicates that the value of a flag is invalid.
 ///
 /// This enum is marked as `non_exhaustive`, which means that additional error variants
 /// may be added in the future without breaking existing code.
@@ -23,6 +24,9 @@ pub enum ParseArgsError {
 
     /// Flag were specified multiple times.
     DuplicateFlag(String),
+
+    /// The value of a given flag is invalid.
+    InvalidValue(String, String),
 }
 
 impl std::fmt::Display for ParseArgsError {
@@ -36,6 +40,9 @@ impl std::fmt::Display for ParseArgsError {
             Self::DuplicateFlag(flag) => {
                 return write!(f, "Flag '{flag}' cannot be specified more than once!")
             }
+            Self::InvalidValue(flag, value) => {
+                return write!(f, "Flag '{flag}' has an invalid value: {value}")
+            }
         }
     }
 }
@@ -75,4 +82,10 @@ mod tests {
             "Flag 'foo' cannot be specified more than once!"
         );
     }
+
+    #[test]
+    fn test_invalid_value_error() {
+        let error = ParseArgsError::InvalidValue(String::from("foo"), String::from("bar"));
+        assert_eq!(format!("{error}"), "Flag 'foo' has an invalid value: bar");
+    }
 }
@@ -134,7 +134,7 @@ impl Filters {
         let match_prefix: bool = self
             .prefixes
             .as_ref() // Convert the Option<&Vec<String>> to an Option<&[String]>
-            .map_or(false, |p| {
+            .is_some_and(|p| {
                 // If there is a prefix list, iterate over it and check if any prefix
                 // is found at the start of `var_name`. If any is found, return `true`.
                 p.iter().any(|item| var_name.starts_with(item))
@@ -144,7 +144,7 @@ impl Filters {
         let match_suffix: bool = self
             .suffixes
             .as_ref() // Convert the Option<&Vec<String>> to an Option<&[String]>
-            .map_or(false, |s| {
+            .is_some_and(|s| {
                 // If there is a suffix list, iterate over it and check if any suffix
                 // is found at the end of `var_name`. If any is found, return `true`.
                 s.iter().any(|item| var_name.ends_with(item))
@@ -154,7 +154,7 @@ impl Filters {
         let match_variable: bool = self
             .variables
             .as_ref() // Convert the Option<&HashSet<String>> to an Option<&HashSet<String>>
-            .map_or(false, |v| {
+            .is_some_and(|v| {
                 // If there is a variable list, check if it contains `var_name`.
                 // If `var_name` is found, return `true`.
                 v.contains(&var_name.to_string())
@@ -271,7 +271,7 @@ mod tests {
     #[test]
     fn test_add_start_parameters() {
         let mut filters = Filters::default();
-        let args = vec!["--help".to_string(), "value".to_string()];
+        let args = ["--help".to_string(), "value".to_string()];
         let mut iter = args.iter();
         let result = filters.add(Filter::Prefix, "--help", None, &mut iter);
         assert!(result.is_err());
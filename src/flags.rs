@@ -5,7 +5,7 @@ use std::collections::HashMap;
 ///
 /// This struct keeps track of the flags that have been set during argument parsing, and ensures that conflicting
 /// or duplicate flags are not allowed. Provides methods for setting and retrieving flags.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Flags {
     flags: HashMap<Flag, FlagItem>,
 }
@@ -44,6 +44,11 @@ pub struct FlagItem {
 /// * `NoEscape`: Disables escape character interpretation.
 /// * `UnbufferedLines`: Enables unbuffered lines mode.
 /// * `Color`: Enables colored output.
+/// * `Directives`: Enables block directives like `#if`.
+/// * `RelaxedNames`: Allows dots and dashes in braced variable names.
+/// * `PreferEnvironment`: Gives the environment precedence over variables from env files.
+/// * `KeepTrailingNewline`: Keeps the trailing newline of variables from env directories.
+/// * `MultilineExpressions`: Allows braced expressions to span multiple lines.
 ///
 /// The enum derives the following traits: `Debug`, `PartialEq`, `Eq`, `Hash`, `Copy`, and `Clone`.
 #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
@@ -58,6 +63,11 @@ pub enum Flag {
     NoEscape,
     UnbufferedLines,
     Color,
+    Directives,
+    RelaxedNames,
+    PreferEnvironment,
+    KeepTrailingNewline,
+    MultilineExpressions,
 }
 
 impl Flags {
@@ -178,7 +188,7 @@ impl Flags {
     pub fn is_flag_set(&self, flag: Flag) -> bool {
         self.flags
             .get(&flag)
-            .map_or(false, |f| f.value.unwrap_or(false))
+            .is_some_and(|f| f.value.unwrap_or(false))
     }
 }
 
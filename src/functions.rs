@@ -0,0 +1,402 @@
+// All functions have the `Apply` signature so that they can be registered in `FUNCTIONS`, even
+// if they cannot fail.
+#![allow(clippy::unnecessary_wraps)]
+
+use crate::encoding;
+use crate::hash;
+use crate::quoting;
+
+/// The implementation of a function: it receives the value and the argument of the function
+/// (an empty string for functions without an argument) and returns the transformed value.
+type Apply = fn(&str, &str) -> Result<String, String>;
+
+/// A named transformation function that can be used in a pipeline like `${VAR | lower | trim}`.
+struct Function {
+    /// The name of the function as used in the pipeline.
+    name: &'static str,
+    /// Whether the function requires an argument, e.g. `default:VALUE`.
+    takes_argument: bool,
+    /// The implementation of the function.
+    apply: Apply,
+}
+
+/// All registered functions. To add a function, add an entry here and document it in the help
+/// text and the README.
+const FUNCTIONS: &[Function] = &[
+    Function {
+        name: "base64",
+        takes_argument: false,
+        apply: base64,
+    },
+    Function {
+        name: "base64_decode",
+        takes_argument: false,
+        apply: base64_decode,
+    },
+    Function {
+        name: "base64url",
+        takes_argument: false,
+        apply: base64url,
+    },
+    Function {
+        name: "base64url_decode",
+        takes_argument: false,
+        apply: base64url_decode,
+    },
+    Function {
+        name: "capitalize",
+        takes_argument: false,
+        apply: capitalize,
+    },
+    Function {
+        name: "crc32",
+        takes_argument: false,
+        apply: crc32,
+    },
+    Function {
+        name: "default",
+        takes_argument: true,
+        apply: default,
+    },
+    Function {
+        name: "hex",
+        takes_argument: false,
+        apply: hex,
+    },
+    Function {
+        name: "hex_decode",
+        takes_argument: false,
+        apply: hex_decode,
+    },
+    Function {
+        name: "json",
+        takes_argument: false,
+        apply: json,
+    },
+    Function {
+        name: "length",
+        takes_argument: false,
+        apply: length,
+    },
+    Function {
+        name: "lower",
+        takes_argument: false,
+        apply: lower,
+    },
+    Function {
+        name: "md5",
+        takes_argument: false,
+        apply: md5,
+    },
+    Function {
+        name: "sha1",
+        takes_argument: false,
+        apply: sha1,
+    },
+    Function {
+        name: "sha256",
+        takes_argument: false,
+        apply: sha256,
+    },
+    Function {
+        name: "shell",
+        takes_argument: false,
+        apply: shell,
+    },
+    Function {
+        name: "trim",
+        takes_argument: false,
+        apply: trim,
+    },
+    Function {
+        name: "upper",
+        takes_argument: false,
+        apply: upper,
+    },
+    Function {
+        name: "urldecode",
+        takes_argument: false,
+        apply: urldecode,
+    },
+    Function {
+        name: "urlencode",
+        takes_argument: false,
+        apply: urlencode,
+    },
+    Function {
+        name: "xml",
+        takes_argument: false,
+        apply: xml,
+    },
+    Function {
+        name: "yaml",
+        takes_argument: false,
+        apply: yaml,
+    },
+];
+
+/// Applies the function with the given `name` to `value`.
+///
+/// # Arguments
+///
+/// * `name` - The name of the function.
+/// * `value` - The value to transform.
+/// * `argument` - The argument of the function, `None` if no argument was given.
+///
+/// # Returns
+///
+/// * `Ok(String)` - The transformed value.
+/// * `Err(String)` - An error string if the function is unknown, if the argument does not match
+///   the function, or if the function fails.
+pub fn apply(name: &str, value: &str, argument: Option<&str>) -> Result<String, String> {
+    let function = FUNCTIONS
+        .iter()
+        .find(|function| function.name == name)
+        .ok_or_else(|| format!("Unknown function: '{name}'"))?;
+
+    match (function.takes_argument, argument) {
+        (true, None) => return Err(format!("Function '{name}' requires an argument")),
+        (false, Some(_)) => return Err(format!("Function '{name}' does not take an argument")),
+        (_, argument) => return (function.apply)(value, argument.unwrap_or_default()),
+    }
+}
+
+/// Returns `true` if a function with the given `name` is registered.
+pub fn is_function(name: &str) -> bool {
+    return FUNCTIONS.iter().any(|function| function.name == name);
+}
+
+/// Converts decoded bytes to a string, as the output of renvsubst is always valid UTF-8.
+fn decoded_to_string(decoded: Vec<u8>) -> Result<String, String> {
+    return String::from_utf8(decoded).map_err(|_| "Decoded value is not valid UTF-8".to_string());
+}
+
+/// Encodes the value as base64.
+fn base64(value: &str, _: &str) -> Result<String, String> {
+    return Ok(encoding::base64_encode(value.as_bytes(), false));
+}
+
+/// Decodes a base64 encoded value.
+fn base64_decode(value: &str, _: &str) -> Result<String, String> {
+    return decoded_to_string(encoding::base64_decode(value, false)?);
+}
+
+/// Encodes the value as URL and filename safe base64.
+fn base64url(value: &str, _: &str) -> Result<String, String> {
+    return Ok(encoding::base64_encode(value.as_bytes(), true));
+}
+
+/// Decodes an URL and filename safe base64 encoded value.
+fn base64url_decode(value: &str, _: &str) -> Result<String, String> {
+    return decoded_to_string(encoding::base64_decode(value, true)?);
+}
+
+/// Converts the first character to uppercase.
+fn capitalize(value: &str, _: &str) -> Result<String, String> {
+    let mut chars = value.chars();
+    match chars.next() {
+        Some(first) => return Ok(first.to_uppercase().chain(chars).collect()),
+        None => return Ok(String::new()),
+    }
+}
+
+/// Uses `argument` if the value is empty.
+fn default(value: &str, argument: &str) -> Result<String, String> {
+    if value.is_empty() {
+        return Ok(argument.to_string());
+    }
+    return Ok(value.to_string());
+}
+
+/// Computes the CRC-32 checksum of the value as hexadecimal string.
+fn crc32(value: &str, _: &str) -> Result<String, String> {
+    return Ok(encoding::hex_encode(&hash::crc32(value.as_bytes())));
+}
+
+/// Encodes the value as hexadecimal string.
+fn hex(value: &str, _: &str) -> Result<String, String> {
+    return Ok(encoding::hex_encode(value.as_bytes()));
+}
+
+/// Decodes a hexadecimal string.
+fn hex_decode(value: &str, _: &str) -> Result<String, String> {
+    return decoded_to_string(encoding::hex_decode(value)?);
+}
+
+/// Quotes the value as a JSON string literal.
+fn json(value: &str, _: &str) -> Result<String, String> {
+    return Ok(quoting::json(value));
+}
+
+/// Returns the number of characters.
+fn length(value: &str, _: &str) -> Result<String, String> {
+    return Ok(value.chars().count().to_string());
+}
+
+/// Converts all characters to lowercase.
+fn lower(value: &str, _: &str) -> Result<String, String> {
+    return Ok(value.to_lowercase());
+}
+
+/// Computes the MD5 digest of the value as hexadecimal string.
+fn md5(value: &str, _: &str) -> Result<String, String> {
+    return Ok(encoding::hex_encode(&hash::md5(value.as_bytes())));
+}
+
+/// Computes the SHA-1 digest of the value as hexadecimal string.
+fn sha1(value: &str, _: &str) -> Result<String, String> {
+    return Ok(encoding::hex_encode(&hash::sha1(value.as_bytes())));
+}
+
+/// Computes the SHA-256 digest of the value as hexadecimal string.
+fn sha256(value: &str, _: &str) -> Result<String, String> {
+    return Ok(encoding::hex_encode(&hash::sha256(value.as_bytes())));
+}
+
+/// Quotes the value for use as a single word in a POSIX shell.
+fn shell(value: &str, _: &str) -> Result<String, String> {
+    return Ok(quoting::shell(value));
+}
+
+/// Removes leading and trailing whitespace.
+fn trim(value: &str, _: &str) -> Result<String, String> {
+    return Ok(value.trim().to_string());
+}
+
+/// Converts all characters to uppercase.
+fn upper(value: &str, _: &str) -> Result<String, String> {
+    return Ok(value.to_uppercase());
+}
+
+/// Decodes a percent-encoded value.
+fn urldecode(value: &str, _: &str) -> Result<String, String> {
+    return decoded_to_string(encoding::url_decode(value)?);
+}
+
+/// Percent-encodes the value for use in an URL.
+fn urlencode(value: &str, _: &str) -> Result<String, String> {
+    return Ok(encoding::url_encode(value));
+}
+
+/// Escapes the value for use in XML or HTML.
+fn xml(value: &str, _: &str) -> Result<String, String> {
+    return Ok(quoting::xml(value));
+}
+
+/// Quotes the value as a double-quoted YAML scalar.
+fn yaml(value: &str, _: &str) -> Result<String, String> {
+    return Ok(quoting::yaml(value));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_functions_are_sorted() {
+        assert!(FUNCTIONS.windows(2).all(|w| w[0].name < w[1].name));
+    }
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(
+            apply("lower", "Hello Wörld", None),
+            Ok("hello wörld".to_string())
+        );
+        assert_eq!(
+            apply("upper", "Hello Wörld", None),
+            Ok("HELLO WÖRLD".to_string())
+        );
+        assert_eq!(apply("capitalize", "élan", None), Ok("Élan".to_string()));
+        assert_eq!(apply("capitalize", "", None), Ok(String::new()));
+        assert_eq!(apply("trim", "  value \n", None), Ok("value".to_string()));
+        assert_eq!(apply("length", "äöü", None), Ok("3".to_string()));
+        assert_eq!(apply("default", "", Some("x")), Ok("x".to_string()));
+        assert_eq!(
+            apply("default", "value", Some("x")),
+            Ok("value".to_string())
+        );
+    }
+
+    #[test]
+    fn test_apply_encoding() {
+        assert_eq!(
+            apply("base64", "s3cr3t!", None),
+            Ok("czNjcjN0IQ==".to_string())
+        );
+        assert_eq!(
+            apply("base64_decode", "czNjcjN0IQ==", None),
+            Ok("s3cr3t!".to_string())
+        );
+        assert_eq!(apply("base64url", "ü?", None), Ok("w7w_".to_string()));
+        assert_eq!(
+            apply("base64url_decode", "w7w_", None),
+            Ok("ü?".to_string())
+        );
+        assert_eq!(apply("hex", "ü", None), Ok("c3bc".to_string()));
+        assert_eq!(apply("hex_decode", "C3BC", None), Ok("ü".to_string()));
+        assert_eq!(
+            apply("urlencode", "p@ss:w/rd", None),
+            Ok("p%40ss%3Aw%2Frd".to_string())
+        );
+        assert_eq!(
+            apply("urldecode", "p%40ss%3Aw%2Frd", None),
+            Ok("p@ss:w/rd".to_string())
+        );
+        assert_eq!(
+            apply("hex_decode", "ff", None),
+            Err("Decoded value is not valid UTF-8".to_string())
+        );
+    }
+
+    #[test]
+    fn test_apply_quoting() {
+        let value = "it's \"quoted\": <ok> & \\";
+        assert_eq!(
+            apply("json", value, None),
+            Ok("\"it's \\\"quoted\\\": <ok> & \\\\\"".to_string())
+        );
+        assert_eq!(apply("yaml", value, None), apply("json", value, None));
+        assert_eq!(
+            apply("shell", value, None),
+            Ok("'it'\\''s \"quoted\": <ok> & \\'".to_string())
+        );
+        assert_eq!(
+            apply("xml", value, None),
+            Ok("it&#39;s &quot;quoted&quot;: &lt;ok&gt; &amp; \\".to_string())
+        );
+    }
+
+    #[test]
+    fn test_apply_hash() {
+        assert_eq!(
+            apply("sha256", "abc", None),
+            Ok("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string())
+        );
+        assert_eq!(
+            apply("sha1", "abc", None),
+            Ok("a9993e364706816aba3e25717850c26c9cd0d89d".to_string())
+        );
+        assert_eq!(
+            apply("md5", "abc", None),
+            Ok("900150983cd24fb0d6963f7d28e17f72".to_string())
+        );
+        assert_eq!(apply("crc32", "abc", None), Ok("352441c2".to_string()));
+    }
+
+    #[test]
+    fn test_apply_errors() {
+        assert_eq!(
+            apply("unknown", "value", None),
+            Err("Unknown function: 'unknown'".to_string())
+        );
+        assert_eq!(
+            apply("default", "value", None),
+            Err("Function 'default' requires an argument".to_string())
+        );
+        assert_eq!(
+            apply("lower", "value", Some("x")),
+            Err("Function 'lower' does not take an argument".to_string())
+        );
+    }
+}
@@ -0,0 +1,335 @@
+/// A single element of a parsed glob pattern.
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Token {
+    /// A literal character.
+    Literal(char),
+    /// `?` matches any single character.
+    AnyChar,
+    /// `*` matches any sequence of characters, including the empty sequence.
+    AnyString,
+    /// `[...]` matches one character out of a set of characters and ranges.
+    /// The boolean is `true` if the class is negated (`[!...]` or `[^...]`).
+    Class(bool, Vec<(char, char)>),
+}
+
+/// `Pattern` is a shell glob pattern as used by the bash parameter expansion operators.
+///
+/// The following syntax is supported:
+/// * `*` matches any sequence of characters, including the empty sequence.
+/// * `?` matches any single character.
+/// * `[abc]`, `[a-z]` match one character out of the set. `[!abc]` or `[^abc]` negate the set.
+/// * `\` escapes the following character, which is then matched literally.
+///
+/// Every other character matches itself. Matching works on Unicode scalar values, not on bytes.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+}
+
+impl Pattern {
+    /// Parses a glob pattern.
+    ///
+    /// Invalid constructs, like an unterminated `[` or a trailing `\`, are matched literally, as
+    /// bash does.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern`: The glob pattern to parse.
+    ///
+    /// # Returns
+    ///
+    /// The parsed `Pattern`.
+    ///
+    pub fn new(pattern: &str) -> Self {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            match chars[i] {
+                '\\' if i + 1 < chars.len() => {
+                    tokens.push(Token::Literal(chars[i + 1]));
+                    i += 2;
+                    continue;
+                }
+                '*' => {
+                    // Consecutive stars are equivalent to a single one
+                    if tokens.last() != Some(&Token::AnyString) {
+                        tokens.push(Token::AnyString);
+                    }
+                }
+                '?' => tokens.push(Token::AnyChar),
+                '[' => {
+                    if let Some((class, next)) = Self::parse_class(&chars, i) {
+                        tokens.push(class);
+                        i = next;
+                        continue;
+                    }
+                    tokens.push(Token::Literal('['));
+                }
+                c => tokens.push(Token::Literal(c)),
+            }
+            i += 1;
+        }
+
+        return Pattern { tokens };
+    }
+
+    /// Parses a bracket expression starting at `start` (the position of the `[`).
+    ///
+    /// Returns the parsed class and the position after the closing `]`, or `None` if the
+    /// bracket expression is not terminated.
+    fn parse_class(chars: &[char], start: usize) -> Option<(Token, usize)> {
+        let mut i = start + 1;
+        let negated = matches!(chars.get(i), Some('!' | '^'));
+        if negated {
+            i += 1;
+        }
+
+        let mut ranges = Vec::new();
+        let mut first = true;
+
+        while i < chars.len() {
+            let mut c = chars[i];
+
+            // A ']' directly after the opening bracket is part of the set
+            if c == ']' && !first {
+                return Some((Token::Class(negated, ranges), i + 1));
+            }
+            first = false;
+
+            if c == '\\' && i + 1 < chars.len() {
+                i += 1;
+                c = chars[i];
+            }
+
+            // Check for a range like 'a-z'
+            if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&end| end != ']') {
+                let mut end_index = i + 2;
+                if chars[end_index] == '\\' && end_index + 1 < chars.len() {
+                    end_index += 1;
+                }
+                ranges.push((c, chars[end_index]));
+                i = end_index + 1;
+                continue;
+            }
+
+            ranges.push((c, c));
+            i += 1;
+        }
+
+        return None;
+    }
+
+    /// Checks whether the pattern matches the whole of `text`.
+    ///
+    /// # Arguments
+    ///
+    /// * `text`: The characters to match against.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the pattern matches all of `text`, otherwise `false`.
+    ///
+    pub fn matches(&self, text: &[char]) -> bool {
+        let tokens = &self.tokens;
+        let (mut t, mut p) = (0, 0);
+        // Position of the last '*' in the pattern and the text position it currently covers
+        let mut backtrack: Option<(usize, usize)> = None;
+
+        while t < text.len() {
+            match tokens.get(p) {
+                Some(Token::AnyString) => {
+                    backtrack = Some((p, t));
+                    p += 1;
+                    continue;
+                }
+                Some(token) if Self::matches_char(token, text[t]) => {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+                _ => {}
+            }
+
+            // Mismatch: let the last '*' swallow one more character
+            match backtrack {
+                Some((star, covered)) => {
+                    backtrack = Some((star, covered + 1));
+                    p = star + 1;
+                    t = covered + 1;
+                }
+                None => return false,
+            }
+        }
+
+        // The remaining pattern may only consist of '*'
+        return tokens[p..].iter().all(|t| *t == Token::AnyString);
+    }
+
+    /// Checks whether a single, non-'*' token matches the character `c`.
+    fn matches_char(token: &Token, c: char) -> bool {
+        match token {
+            Token::Literal(l) => return *l == c,
+            Token::AnyChar => return true,
+            Token::Class(negated, ranges) => {
+                let found = ranges.iter().any(|(start, end)| *start <= c && c <= *end);
+                return found != *negated;
+            }
+            Token::AnyString => return false,
+        }
+    }
+
+    /// Finds the shortest or longest prefix of `text` matched by the pattern.
+    ///
+    /// # Arguments
+    ///
+    /// * `text`: The characters to match against.
+    /// * `longest`: `true` to find the longest match, `false` to find the shortest match.
+    ///
+    /// # Returns
+    ///
+    /// The number of characters of the matched prefix, or `None` if no prefix matches.
+    ///
+    pub fn match_prefix(&self, text: &[char], longest: bool) -> Option<usize> {
+        if longest {
+            return (0..=text.len())
+                .rev()
+                .find(|&end| self.matches(&text[..end]));
+        }
+        return (0..=text.len()).find(|&end| self.matches(&text[..end]));
+    }
+
+    /// Finds the shortest or longest suffix of `text` matched by the pattern.
+    ///
+    /// # Arguments
+    ///
+    /// * `text`: The characters to match against.
+    /// * `longest`: `true` to find the longest match, `false` to find the shortest match.
+    ///
+    /// # Returns
+    ///
+    /// The start index of the matched suffix, or `None` if no suffix matches.
+    ///
+    pub fn match_suffix(&self, text: &[char], longest: bool) -> Option<usize> {
+        if longest {
+            return (0..=text.len()).find(|&start| self.matches(&text[start..]));
+        }
+        return (0..=text.len())
+            .rev()
+            .find(|&start| self.matches(&text[start..]));
+    }
+}
+
+/// Escapes `text` so that it is matched literally when used as a glob pattern.
+///
+/// Every character that is not alphanumeric is prefixed with a backslash. The result can also be
+/// used as a replacement string, where a backslash escapes the following character as well.
+///
+/// # Arguments
+///
+/// * `text`: The text to escape.
+///
+/// # Returns
+///
+/// The escaped text.
+///
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    return escaped;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text: &str) -> Vec<char> {
+        return text.chars().collect();
+    }
+
+    #[test]
+    fn test_matches_literal() {
+        let pattern = Pattern::new("hello");
+        assert!(pattern.matches(&chars("hello")));
+        assert!(!pattern.matches(&chars("hell")));
+        assert!(!pattern.matches(&chars("hello!")));
+    }
+
+    #[test]
+    fn test_matches_wildcards() {
+        let pattern = Pattern::new("*.tar.?z");
+        assert!(pattern.matches(&chars("archive.tar.gz")));
+        assert!(pattern.matches(&chars(".tar.xz")));
+        assert!(!pattern.matches(&chars("archive.tar.bz2")));
+
+        let pattern = Pattern::new("a*b*c");
+        assert!(pattern.matches(&chars("abc")));
+        assert!(pattern.matches(&chars("aXbYbZc")));
+        assert!(!pattern.matches(&chars("aXbYbZ")));
+
+        assert!(Pattern::new("*").matches(&chars("")));
+        assert!(Pattern::new("").matches(&chars("")));
+        assert!(!Pattern::new("?").matches(&chars("")));
+    }
+
+    #[test]
+    fn test_matches_classes() {
+        let pattern = Pattern::new("[a-c]x[!0-9]");
+        assert!(pattern.matches(&chars("bxy")));
+        assert!(!pattern.matches(&chars("dxy")));
+        assert!(!pattern.matches(&chars("bx1")));
+
+        let pattern = Pattern::new("[]a]");
+        assert!(pattern.matches(&chars("]")));
+        assert!(pattern.matches(&chars("a")));
+
+        let pattern = Pattern::new("[^ä]");
+        assert!(!pattern.matches(&chars("ä")));
+        assert!(pattern.matches(&chars("a")));
+
+        // An unterminated class is matched literally
+        let pattern = Pattern::new("[abc");
+        assert!(pattern.matches(&chars("[abc")));
+    }
+
+    #[test]
+    fn test_matches_escapes() {
+        let pattern = Pattern::new("\\*\\?\\[a]");
+        assert!(pattern.matches(&chars("*?[a]")));
+        assert!(!pattern.matches(&chars("x?[a]")));
+
+        // A trailing backslash is matched literally
+        assert!(Pattern::new("a\\").matches(&chars("a\\")));
+    }
+
+    #[test]
+    fn test_match_prefix() {
+        let text = chars("registry.example.com/team/app:1.0");
+        let pattern = Pattern::new("*/");
+        assert_eq!(pattern.match_prefix(&text, false), Some(21));
+        assert_eq!(pattern.match_prefix(&text, true), Some(26));
+        assert_eq!(Pattern::new("x*").match_prefix(&text, false), None);
+    }
+
+    #[test]
+    fn test_match_suffix() {
+        let text = chars("https://example.com/path?query=1?x");
+        let pattern = Pattern::new("\\?*");
+        assert_eq!(pattern.match_suffix(&text, false), Some(32));
+        assert_eq!(pattern.match_suffix(&text, true), Some(24));
+        assert_eq!(Pattern::new("*x.y").match_suffix(&text, true), None);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a*b/c"), "a\\*b\\/c");
+        assert!(Pattern::new(&escape("[x]?*")).matches(&chars("[x]?*")));
+        assert!(!Pattern::new(&escape("*")).matches(&chars("abc")));
+    }
+}
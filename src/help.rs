@@ -21,6 +21,12 @@ Flags:
                                    Green for found variables, yellow for default values,
                                    and red for not found variables. Use '--no-replace-unset'
                                    to show not found variables; otherwise, they won't be displayed.
+      --relaxed-names              Allow dots and dashes in braced variable names, e.g. '${db.host}'.
+                                   The name is looked up with dots and dashes replaced by
+                                   underscores and in uppercase, e.g. 'DB_HOST'. Filters and
+                                   error messages use the name as written.
+                                   A dash is only part of a name with a dot, e.g.
+                                   '${server.max-size}', so '${VAR-default}' is still a default.
 
 When the same flag is provided multiple times, renvsubst will throw an error.
 
@@ -36,6 +42,70 @@ Filters:
 The variables will be substituted according to the specified prefix, suffix, or variable name. If none of these options are provided, all variables will be substituted. When one or more options are specified, only variables that match the given prefix, suffix, or variable name will be replaced, while all others will remain unchanged.
 If multiple identical prefixes, suffixes, or variables are provided, only one copy of each will be used.
 
+Expressions:
+      --multiline-expressions      Allow braced expressions to span multiple lines, e.g. '${VAR:-'
+                                   with the closing '}' on a later line. By default, every line is
+                                   replaced on its own.
+      --max-expression-length[=N]  Maximum number of characters of a braced expression that
+                                   spans multiple lines with '--multiline-expressions'. For longer
+                                   expressions and expressions that are never closed, only the
+                                   line with the '${' is kept as is. Defaults to 4096.
+
+Dialects:
+      --dialect[=NAME]             The syntax of variables in the input. Defaults to 'shell'.
+                                   shell:    '$VAR', '${VAR}' and '$((EXPRESSION))'
+                                   mustache: '{{VAR}}' or '{{ VAR }}'
+                                   autoconf: '@VAR@'
+                                   batch:    '%VAR%'
+                                   The text between the delimiters supports the same operators
+                                   as '${...}', e.g. '@VAR:-default@'. Delimiters that do not
+                                   enclose a variable name, optionally followed by an operator
+                                   or a pipeline, are kept as is, e.g. '@alice and @bob'.
+                                   A doubled opening delimiter is a literal delimiter, e.g.
+                                   '%%PATH%' results in '%PATH%'. Nested expressions must use
+                                   '${...}', e.g. '{{A:-${B}}}'. Operators that contain the
+                                   delimiter, e.g. '%FILE%%.txt%', result in an error.
+
+Directives:
+      --directives                 Evaluate block directives. Directive lines are removed from the output.
+      --directive-prefix[=PREFIX]  The prefix of directives. Defaults to '#'.
+      --include-root[=DIR]         The directory that included files must be located in.
+                                   Defaults to the directory of the input file, or the current
+                                   working directory for stdin.
+  #if VAR                          Keep the following lines if '$VAR' is set and not empty.
+  #if !VAR                         Keep the following lines if '$VAR' is unset or empty.
+  #if VAR == value                 Keep the following lines if '$VAR' equals 'value'.
+  #if VAR != value                 Keep the following lines if '$VAR' does not equal 'value'.
+                                   'value' can be quoted and can contain '$VAR' expressions.
+  #else                            Keep the following lines if the condition is false.
+  #endif                           End the '#if' block.
+  #each NAME in LIST               Repeat the following lines for every item of 'LIST', with '$NAME'
+                                   set to the item. 'LIST' is expanded like 'value' and split on
+                                   commas and whitespace. Use 'by \"SEPARATOR\"' after 'LIST' to
+                                   split on 'SEPARATOR' instead.
+  #end                             End the '#each' block. Blocks can be nested.
+  #include PATH                    Render the template file 'PATH', relative to the including file.
+                                   Included files can contain directives, but not include themselves.
+
+Variable sources:
+      --env-file[=PATH]...         Load variables from a dotenv file. Can be specified multiple times;
+                                   later files override earlier ones.
+      --env-dir[=PATH]...          Load variables from a directory, e.g. a mounted Kubernetes Secret.
+                                   Every regular file becomes a variable named after the file.
+                                   Entries starting with '..', like '..data', are skipped.
+                                   Can be specified multiple times.
+      --keep-trailing-newline      Keep the trailing newline of the files of env directories.
+                                   By default, one trailing newline is removed.
+      --prefer-env                 Variables of the environment take precedence over variables of
+                                   env files and env directories. By default, they take precedence
+                                   over the environment.
+  Env files and env directories are loaded in the order they are specified; later ones override
+  earlier ones.
+  Env files contain one 'NAME=value' per line, optionally prefixed with 'export'. Lines starting
+  with '#' are comments, as is a '#' preceded by whitespace after an unquoted value. Values in
+  single quotes are taken literally. In values in double quotes, '\\n', '\\r', '\\t', '\\\"', '\\\\'
+  and '\\$' are escape sequences. Quoted values can span multiple lines and are not expanded.
+
 Input:
   -i, --input[=FILE]               Input file path. Use '-' to read from stdin.
                                    Defaults to stdin if omitted.
@@ -49,19 +119,97 @@ General:
       --version                    Show the version of the program.
 
 Substitution functions:
-  ${VAR:-default}                  Set '$VAR' to 'default' if '$VAR' is unset.
+  ${VAR:-default}                  Set '$VAR' to 'default' if '$VAR' is unset or empty.
+  ${VAR-default}                   Set '$VAR' to 'default' if '$VAR' is unset.
+  ${VAR:=default}                  Assign 'default' to '$VAR' if '$VAR' is unset or empty.
+                                   Later references to '$VAR' resolve to the assigned value.
+  ${VAR=default}                   Assign 'default' to '$VAR' if '$VAR' is unset.
+  ${VAR:?message}                  Fail with 'message' if '$VAR' is unset or empty.
+  ${VAR?message}                   Fail with 'message' if '$VAR' is unset.
+  ${VAR:+alternate}                Use 'alternate' if '$VAR' is set and not empty, otherwise an empty string.
+  ${VAR+alternate}                 Use 'alternate' if '$VAR' is set, otherwise an empty string.
+  ${#VAR}                          The number of characters of '$VAR'.
+  ${!REF}                          The value of the variable whose name is stored in '$REF'.
+                                   Operations apply to that variable, e.g. '${!REF:-default}'.
+  ${!PREFIX*}                      The names of all variables starting with 'PREFIX', separated by spaces.
+  ${!PREFIX@}                      Same as '${!PREFIX*}'.
   ${VAR,}                          Change the first character of '$VAR' to lowercase.
   ${VAR,,}                         Change all characters of '$VAR' to lowercase.
   ${VAR^}                          Change the first character of '$VAR' to uppercase.
   ${VAR^^}                         Change all characters of '$VAR' to uppercase.
+  ${VAR~}                          Toggle the case of the first character of '$VAR'.
+  ${VAR~~}                         Toggle the case of all characters of '$VAR'.
+  ${VAR^^pattern}                  Change all characters of '$VAR' matching 'pattern' to uppercase.
+                                   All case operators accept a pattern, e.g. '${VAR,[A-M]}'.
+  ${VAR@Q}                         Quote '$VAR' in single quotes for re-use as shell input.
+  ${VAR@U}                         Change all characters of '$VAR' to uppercase.
+  ${VAR@u}                         Change the first character of '$VAR' to uppercase.
+  ${VAR@L}                         Change all characters of '$VAR' to lowercase.
+  ${VAR@E}                         Expand backslash escape sequences in '$VAR', e.g. '\\n' and '\\t'.
   ${VAR/pattern/replacement}       Replace first 'pattern' with 'replacement' in VAR.
   ${VAR//pattern/replacement}      Replace all 'pattern' with 'replacement' in VAR.
   ${VAR/#pattern/replacement}      Replace 'pattern' with 'replacement' if VAR starts with it.
   ${VAR/%pattern/replacement}      Replace 'pattern' with 'replacement' if VAR ends with it.
   ${VAR:offset}                    Shift '$VAR' by 'n' characters from the start.
+                                   A negative offset counts from the end, e.g. '${VAR: -4}'.
   ${VAR:offset:length}             Shift '$VAR' by 'n' characters with a maximum length of 'len'.
+                                   A negative length removes 'len' characters from the end.
   ${VAR#pattern}                   Remove the shortest match of 'pattern' from the start of '$VAR'.
+  ${VAR##pattern}                  Remove the longest match of 'pattern' from the start of '$VAR'.
   ${VAR%pattern}                   Remove the shortest match of 'pattern' from the end of '$VAR'.
+  ${VAR%%pattern}                  Remove the longest match of 'pattern' from the end of '$VAR'.
+
+Patterns:
+  The patterns of '#', '##', '%', '%%', '/' and the case operators ',', '^' and '~' are shell
+  glob patterns. The case operators match the pattern against single characters.
+  '*' matches any string, '?' matches any single character and '[...]' matches one of the
+  enclosed characters ('[a-z]' for ranges, '[!...]' to negate). Use '\\' to match any of
+  these characters literally.
+  In the 'replacement' of '/', '&' refers to the matched text. Use '\\&' for a literal '&'.
+
+Functions:
+  Braced variables can be passed through a pipeline of functions, e.g. '${HOST | lower | trim}'.
+  The functions are applied from left to right. Arguments follow a colon, e.g. 'default:VALUE'.
+  The first part of the pipeline can use the substitution functions above, e.g. '${VAR:-x | upper}'.
+  Only a '|' with whitespace on both sides separates functions, e.g. '${VAR:-json|yaml}' is not a
+  pipeline. After a substitution function, all following parts must be functions, so
+  '${VAR:-read | write}' is not a pipeline either.
+  Use '\\|' for a literal '|' in a pipeline. A function after a '|' without whitespace, e.g.
+  '${HOST|lower}', results in an error.
+  base64, base64_decode            Encode or decode the value as base64.
+  base64url, base64url_decode      Encode or decode the value as URL and filename safe base64.
+  capitalize                       Change the first character to uppercase.
+  crc32                            The CRC-32 checksum of the value as hexadecimal string.
+  default:VALUE                    Use 'VALUE' if the value is empty.
+  hex, hex_decode                  Encode or decode the value as hexadecimal string.
+  json                             Quote the value as a JSON string literal, including the quotes.
+  length                           The number of characters.
+  lower                            Change all characters to lowercase.
+  md5, sha1, sha256                The MD5, SHA-1 or SHA-256 digest of the value as hexadecimal string.
+  shell                            Quote the value in single quotes for use in a POSIX shell.
+  trim                             Remove leading and trailing whitespace.
+  upper                            Change all characters to uppercase.
+  urlencode, urldecode             Percent-encode or decode the value for use in an URL.
+  xml                              Escape '&', '<', '>', '\"' and ''' for use in XML or HTML.
+  yaml                             Quote the value as a double-quoted YAML scalar, including the quotes.
+  Decoding fails if the value is not valid or does not decode to valid UTF-8.
+
+Arithmetic:
+  $((expression))                  Evaluate an integer arithmetic expression, e.g. '$((PORT + 1))'.
+                                   Variables can be used with or without '$' and must be set to an
+                                   integer. Supports the operators of bash, e.g. '+', '-', '*', '/',
+                                   '%', '**', comparisons, '&&', '||' and 'a ? b : c'.
+                                   Division by zero, overflows and unset variables are errors.
+                                   The expression is kept as is if a variable is excluded by
+                                   the filters or not replaced because of the '--no-replace' flags.
+                                   Expressions can have up to 1000 tokens and up to 100
+                                   nested parentheses or operators.
+
+Nested expressions:
+  Default values, alternate values, messages, patterns and replacements can contain '$VAR' and
+  '${...}' expressions, e.g. '${SERVICE_URL:-${GLOBAL_URL:-http://localhost}}'.
+  Variables in patterns and replacements are matched literally.
+  Expressions can be nested up to 100 levels deep.
 
 Colors:
   Green represents variables that were successfully substituted.
@@ -286,7 +286,7 @@ mod tests {
     #[test]
     fn test_input_output_set_input_missing_value_error() {
         let mut io = InputOutput::default();
-        let args = vec!["--input".to_string(), "value".to_string()];
+        let args = ["--input".to_string(), "value".to_string()];
         let mut iter = args.iter();
 
         let result = io.set(IO::Input, "--input", None, &mut iter);
@@ -300,7 +300,7 @@ mod tests {
     #[test]
     fn test_input_output_set_output_missing_value_error() {
         let mut io = InputOutput::default();
-        let args = vec!["--output".to_string(), "value".to_string()];
+        let args = ["--output".to_string(), "value".to_string()];
         let mut iter = args.iter();
 
         let result = io.set(IO::Input, "--output", None, &mut iter);
@@ -1,12 +1,26 @@
+// Explicit returns are the preferred style in this code base
+#![allow(clippy::needless_return)]
+
 mod args;
+mod arithmetic;
+mod directives;
+mod dotenv;
+mod encoding;
+mod env_dir;
 mod env_subst;
 mod errors;
 mod filters;
 mod flags;
+mod functions;
+mod glob;
+mod hash;
 mod help;
 mod io;
+mod quoting;
+mod settings;
 mod utils;
 mod variable_expansion;
+mod variables;
 
 use crate::args::Args;
 use crate::env_subst::process_input;
@@ -51,12 +65,23 @@ fn run(args: &[String]) -> Result<(), String> {
     if parsed_args
         .io
         .get(IO::Output)
-        .map_or(false, |s| s.as_str() != "-")
+        .is_some_and(|s| s.as_str() != "-")
     {
         parsed_args.flags.update(Flag::Color, false);
     }
 
-    process_input(input, output, &parsed_args.flags, &parsed_args.filters)
+    // Includes are resolved relative to the input file
+    if let Some(input_file) = parsed_args.io.get(IO::Input).filter(|s| s.as_str() != "-") {
+        parsed_args.settings.set_input_file(input_file);
+    }
+
+    process_input(
+        input,
+        output,
+        &parsed_args.flags,
+        &parsed_args.filters,
+        &parsed_args.settings,
+    )
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -166,6 +191,28 @@ mod tests {
         assert_eq!(contents, "Hello, world!"); // check if output file contains the correct text
     }
 
+    #[test]
+    fn test_run_include_relative_to_input_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_file_path = dir.path().join("main.tmpl");
+        let output_file_path = dir.path().join("output.txt");
+        std::fs::write(&input_file_path, "#include fragment.tmpl\nmain\n").unwrap();
+        std::fs::write(dir.path().join("fragment.tmpl"), "fragment\n").unwrap();
+
+        let args = vec![
+            String::from("--directives"),
+            String::from("--input"),
+            input_file_path.to_str().unwrap().to_string(),
+            String::from("--output"),
+            output_file_path.to_str().unwrap().to_string(),
+        ];
+        let result = run(&args);
+
+        assert!(result.is_ok());
+        let contents = std::fs::read_to_string(output_file_path).unwrap();
+        assert_eq!(contents, "fragment\nmain\n");
+    }
+
     #[test]
     fn test_run_output_error() {
         let args = vec![
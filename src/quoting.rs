@@ -0,0 +1,242 @@
+use std::fmt::Write;
+
+/// Quotes `value` as a JSON string literal, including the surrounding double quotes.
+///
+/// Double quotes, backslashes and control characters are escaped.
+///
+/// # Arguments
+///
+/// * `value`: The value to quote.
+///
+/// # Returns
+///
+/// The quoted value, e.g. `"say \"hi\""` for `say "hi"`.
+///
+pub fn json(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '\u{8}' => quoted.push_str("\\b"),
+            '\u{c}' => quoted.push_str("\\f"),
+            c if c.is_control() && c < '\u{80}' => {
+                // Writing to a string cannot fail
+                let _ = write!(quoted, "\\u{:04x}", u32::from(c));
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    return quoted;
+}
+
+/// Quotes `value` as a double-quoted YAML scalar, including the surrounding double quotes.
+///
+/// A double-quoted scalar is always interpreted as a string, so values like `yes`, `1.0` or
+/// `key: value` keep their meaning. Besides the escapes of `json`, the YAML line breaks
+/// U+0085, U+2028 and U+2029 and all other control characters are escaped.
+///
+/// # Arguments
+///
+/// * `value`: The value to quote.
+///
+/// # Returns
+///
+/// The quoted value, e.g. `"key: value"` for `key: value`.
+///
+pub fn yaml(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    for c in json(value).chars() {
+        match c {
+            '\u{85}' => quoted.push_str("\\N"),
+            '\u{2028}' => quoted.push_str("\\L"),
+            '\u{2029}' => quoted.push_str("\\P"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\x{:02x}", u32::from(c));
+            }
+            c => quoted.push(c),
+        }
+    }
+    return quoted;
+}
+
+/// Quotes `value` for use as a single word in a POSIX shell.
+///
+/// The value is enclosed in single quotes, in which no character has a special meaning. Single
+/// quotes in the value are written as `'\''`.
+///
+/// # Arguments
+///
+/// * `value`: The value to quote.
+///
+/// # Returns
+///
+/// The quoted value, e.g. `'it'\''s'` for `it's`.
+///
+pub fn shell(value: &str) -> String {
+    return format!("'{}'", value.replace('\'', "'\\''"));
+}
+
+/// Escapes `value` for use in XML or HTML text and attribute values.
+///
+/// # Arguments
+///
+/// * `value`: The value to escape.
+///
+/// # Returns
+///
+/// The value with `&`, `<`, `>`, `"` and `'` replaced by entities.
+///
+pub fn xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    return escaped;
+}
+
+/// Expands the backslash escape sequences in `value`, like the `$'...'` quoting of bash.
+///
+/// The following sequences are supported: `\a`, `\b`, `\e`, `\E`, `\f`, `\n`, `\r`, `\t`,
+/// `\v`, `\\`, `\'`, `\"`, `\?`, `\NNN` (one to three octal digits), `\xHH` (one or two
+/// hexadecimal digits), `\uHHHH` and `\UHHHHHHHH` (Unicode code points) and `\cX` (the control
+/// character of `X`). Other backslashes are kept as is.
+///
+/// # Arguments
+///
+/// * `value`: The value to expand.
+///
+/// # Returns
+///
+/// The value with the escape sequences replaced by the characters they stand for.
+///
+pub fn expand_escapes(value: &str) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            expanded.push(c);
+            continue;
+        }
+
+        let Some(escape) = chars.next() else {
+            expanded.push('\\');
+            break;
+        };
+
+        let simple = match escape {
+            'a' => Some('\u{7}'),
+            'b' => Some('\u{8}'),
+            'e' | 'E' => Some('\u{1b}'),
+            'f' => Some('\u{c}'),
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            't' => Some('\t'),
+            'v' => Some('\u{b}'),
+            '\\' | '\'' | '"' | '?' => Some(escape),
+            'c' => chars
+                .next_if(char::is_ascii)
+                .map(|x| char::from(x.to_ascii_uppercase() as u8 ^ 0x40)),
+            _ => None,
+        };
+        if let Some(simple) = simple {
+            expanded.push(simple);
+            continue;
+        }
+
+        let (radix, max_digits) = match escape {
+            '0'..='7' => (8, 3),
+            'x' => (16, 2),
+            'u' => (16, 4),
+            'U' => (16, 8),
+            _ => {
+                expanded.push('\\');
+                expanded.push(escape);
+                continue;
+            }
+        };
+
+        // The first octal digit is part of the escape, e.g. '\101'
+        let mut digits = String::new();
+        if radix == 8 {
+            digits.push(escape);
+        }
+        while digits.len() < max_digits {
+            match chars.next_if(|c| c.is_digit(radix)) {
+                Some(digit) => digits.push(digit),
+                None => break,
+            }
+        }
+
+        if let Some(character) = u32::from_str_radix(&digits, radix)
+            .ok()
+            .and_then(char::from_u32)
+        {
+            expanded.push(character);
+        } else {
+            // No digits or an invalid code point: keep the text as is
+            expanded.push('\\');
+            expanded.push(escape);
+            expanded.push_str(&digits);
+        }
+    }
+
+    return expanded;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        assert_eq!(json(""), "\"\"");
+        assert_eq!(json("pa\"ss\\word"), "\"pa\\\"ss\\\\word\"");
+        assert_eq!(json("a\nb\tc\u{1}ä"), "\"a\\nb\\tc\\u0001ä\"");
+    }
+
+    #[test]
+    fn test_yaml() {
+        assert_eq!(yaml("key: value"), "\"key: value\"");
+        assert_eq!(yaml("yes"), "\"yes\"");
+        assert_eq!(yaml("a\u{2028}b\u{85}\u{9f}"), "\"a\\Lb\\N\\x9f\"");
+    }
+
+    #[test]
+    fn test_shell() {
+        assert_eq!(shell(""), "''");
+        assert_eq!(shell("it's $HOME"), "'it'\\''s $HOME'");
+    }
+
+    #[test]
+    fn test_expand_escapes() {
+        assert_eq!(expand_escapes("a\\tb\\nc\\\\d\\'"), "a\tb\nc\\d'");
+        assert_eq!(expand_escapes("\\101\\x42\\u00e4\\U0001F600"), "ABä😀");
+        assert_eq!(
+            expand_escapes("\\0\\e[0m\\cA\\c?"),
+            "\0\u{1b}[0m\u{1}\u{7f}"
+        );
+        assert_eq!(expand_escapes("\\q \\xZ \\uD800 \\"), "\\q \\xZ \\uD800 \\");
+    }
+
+    #[test]
+    fn test_xml() {
+        assert_eq!(
+            xml("<a href=\"x\">Tom & Jerry's</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+    }
+}
@@ -0,0 +1,447 @@
+use crate::errors::ParseArgsError;
+use crate::utils::START_PARAMETERS;
+use std::path::{Path, PathBuf};
+
+/// The default maximum number of characters of a braced expression that spans multiple lines.
+pub const DEFAULT_MAX_EXPRESSION_LENGTH: usize = 4096;
+
+/// The default prefix of block directives like `#if`.
+pub const DEFAULT_DIRECTIVE_PREFIX: &str = "#";
+
+/// A struct representing the command-line options that take a value, other than the input,
+/// the output and the filters.
+///
+/// Every field is `None` if the option was not specified, in which case the getter returns the
+/// default value. Options that can be specified multiple times are collected in a `Vec`.
+#[derive(Debug, Default)]
+pub struct Settings {
+    max_expression_length: Option<usize>,
+    directive_prefix: Option<String>,
+    include_root: Option<PathBuf>,
+    input_file: Option<PathBuf>,
+    dialect: Option<Dialect>,
+    variable_sources: Vec<VariableSource>,
+}
+
+/// Setting is an enumeration representing the different settings.
+///
+/// The available settings are:
+/// * `MaxExpressionLength`: The maximum number of characters of a braced expression that spans
+///   multiple lines.
+/// * `DirectivePrefix`: The prefix of block directives like `#if`.
+/// * `IncludeRoot`: The directory that included files must be located in.
+/// * `Dialect`: The syntax of variables in the input.
+/// * `EnvFile`: A dotenv file to load variables from.
+/// * `EnvDir`: A directory with one file per variable to load variables from.
+///
+/// The enum derives the following traits: `Debug`, `PartialEq`, `Eq`, `Hash`, `Copy`, and `Clone`.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+#[non_exhaustive]
+pub enum Setting {
+    /// Maximum length of a braced expression that spans multiple lines
+    MaxExpressionLength,
+    /// Prefix of block directives
+    DirectivePrefix,
+    /// Directory that included files must be located in
+    IncludeRoot,
+    /// Syntax of variables in the input
+    Dialect,
+    /// Dotenv file to load variables from
+    EnvFile,
+    /// Directory with one file per variable to load variables from
+    EnvDir,
+}
+
+/// `VariableSource` is an enumeration of the sources variables can be loaded from, besides the
+/// environment.
+///
+/// * `EnvFile`: A dotenv file, see `dotenv::parse`.
+/// * `EnvDir`: A directory with one file per variable, see `env_dir::load_dir`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum VariableSource {
+    EnvFile(PathBuf),
+    EnvDir(PathBuf),
+}
+
+/// Dialect is an enumeration of the syntaxes of variables in the input.
+///
+/// The available dialects are:
+/// * `Shell`: `$VAR`, `${VAR}` and `$((EXPRESSION))`, the default.
+/// * `Mustache`: `{{VAR}}`, optionally with whitespace inside the braces, e.g. `{{ VAR }}`.
+/// * `Autoconf`: `@VAR@`.
+/// * `Batch`: `%VAR%`.
+///
+/// Except for `Shell`, the content between the delimiters is processed like the content of
+/// `${...}`, so all operators and functions are available, e.g. `@VAR:-default@`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Dialect {
+    Shell,
+    Mustache,
+    Autoconf,
+    Batch,
+}
+
+impl Dialect {
+    /// Returns the dialect with the given command-line `name`, or `None` if it is unknown.
+    fn from_name(name: &str) -> Option<Self> {
+        return match name {
+            "shell" => Some(Dialect::Shell),
+            "mustache" => Some(Dialect::Mustache),
+            "autoconf" => Some(Dialect::Autoconf),
+            "batch" => Some(Dialect::Batch),
+            _ => None,
+        };
+    }
+
+    /// Returns the opening and closing delimiter of variables, or `None` for `Shell`.
+    pub fn delimiters(self) -> Option<(&'static str, &'static str)> {
+        return match self {
+            Dialect::Shell => None,
+            Dialect::Mustache => Some(("{{", "}}")),
+            Dialect::Autoconf => Some(("@", "@")),
+            Dialect::Batch => Some(("%", "%")),
+        };
+    }
+
+    /// Returns `true` if whitespace is allowed around the expression inside the delimiters.
+    pub fn allows_padding(self) -> bool {
+        return self == Dialect::Mustache;
+    }
+}
+
+impl Settings {
+    /// Sets the value of the given `setting` based on the command-line arguments.
+    ///
+    /// # Arguments
+    ///
+    /// * `setting`: A `Setting` enum variant that specifies which setting should be set.
+    /// * `arg`: A `&str` representing the command-line flag argument that was used.
+    /// * `value`: An optional `&str` representing the value that was provided for the command-line
+    ///   flag. If no value is provided, the function tries to get the next argument from the
+    ///   provided `iter`.
+    /// * `iter`: A mutable reference to an iterator over the command-line arguments.
+    ///
+    /// # Returns
+    ///
+    /// A `Result<(), ParseArgsError>` that is `Ok(())` if the setting was successfully set, or a
+    /// `ParseArgsError` if the value is missing or invalid.
+    ///
+    /// # Errors
+    ///
+    /// * `ParseArgsError::MissingValue(arg)` - When the value is missing or is another flag.
+    /// * `ParseArgsError::InvalidValue(arg, value)` - When the value cannot be parsed.
+    pub fn set(
+        &mut self,
+        setting: Setting,
+        arg: &str,
+        value: Option<&str>,
+        iter: &mut std::slice::Iter<String>,
+    ) -> Result<(), ParseArgsError> {
+        let flag_arg: String = value.map_or_else(
+            // If no value is provided... (was not --max-expression-length=100)
+            || {
+                // If not, get the next argument as the value
+                iter.next()
+                    .map(std::string::ToString::to_string) // Convert the value to a string
+                    // Return an error if the value is missing
+                    .ok_or_else(|| ParseArgsError::MissingValue(arg.to_string()))
+            },
+            |s| Ok(s.to_string()), // return the value if it exists
+        )?;
+
+        if START_PARAMETERS.contains(&flag_arg.as_str()) {
+            return Err(ParseArgsError::MissingValue(arg.to_string()));
+        }
+
+        match setting {
+            Setting::MaxExpressionLength => {
+                let length = flag_arg
+                    .parse::<usize>()
+                    .map_err(|_| ParseArgsError::InvalidValue(arg.to_string(), flag_arg))?;
+                self.max_expression_length = Some(length);
+            }
+            Setting::DirectivePrefix => {
+                // The prefix must be followed directly by the keyword, e.g. '#if'
+                if flag_arg.is_empty() || flag_arg.contains(char::is_whitespace) {
+                    return Err(ParseArgsError::InvalidValue(arg.to_string(), flag_arg));
+                }
+                self.directive_prefix = Some(flag_arg);
+            }
+            Setting::IncludeRoot => {
+                if flag_arg.is_empty() {
+                    return Err(ParseArgsError::InvalidValue(arg.to_string(), flag_arg));
+                }
+                self.include_root = Some(PathBuf::from(flag_arg));
+            }
+            Setting::Dialect => {
+                let dialect = Dialect::from_name(&flag_arg)
+                    .ok_or_else(|| ParseArgsError::InvalidValue(arg.to_string(), flag_arg))?;
+                self.dialect = Some(dialect);
+            }
+            Setting::EnvFile | Setting::EnvDir => {
+                if flag_arg.is_empty() {
+                    return Err(ParseArgsError::InvalidValue(arg.to_string(), flag_arg));
+                }
+                let path = PathBuf::from(flag_arg);
+                self.variable_sources.push(if setting == Setting::EnvFile {
+                    VariableSource::EnvFile(path)
+                } else {
+                    VariableSource::EnvDir(path)
+                });
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the maximum number of characters of a braced expression that spans multiple lines.
+    ///
+    /// An expression that is still open after this many characters is left as is. `0` disables
+    /// expressions that span multiple lines.
+    ///
+    /// # Returns
+    ///
+    /// The configured maximum, or `DEFAULT_MAX_EXPRESSION_LENGTH` if it was not specified.
+    ///
+    pub fn max_expression_length(&self) -> usize {
+        return self
+            .max_expression_length
+            .unwrap_or(DEFAULT_MAX_EXPRESSION_LENGTH);
+    }
+
+    /// Returns the prefix of block directives like `#if`.
+    ///
+    /// # Returns
+    ///
+    /// The configured prefix, or `DEFAULT_DIRECTIVE_PREFIX` if it was not specified.
+    ///
+    pub fn directive_prefix(&self) -> &str {
+        return self
+            .directive_prefix
+            .as_deref()
+            .unwrap_or(DEFAULT_DIRECTIVE_PREFIX);
+    }
+
+    /// Returns the syntax of variables in the input.
+    ///
+    /// # Returns
+    ///
+    /// The configured dialect, or `Dialect::Shell` if it was not specified.
+    ///
+    pub fn dialect(&self) -> Dialect {
+        return self.dialect.unwrap_or(Dialect::Shell);
+    }
+
+    /// Returns the env files and env directories to load variables from, in the order they were
+    /// specified.
+    pub fn variable_sources(&self) -> &[VariableSource] {
+        return &self.variable_sources;
+    }
+
+    /// Sets the path of the input file, which is used to resolve includes.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The path of the input file as specified on the command line.
+    ///
+    pub fn set_input_file(&mut self, path: &str) {
+        self.input_file = Some(PathBuf::from(path));
+    }
+
+    /// Returns the path of the input file, or `None` if the input is read from stdin.
+    pub fn input_file(&self) -> Option<&Path> {
+        return self.input_file.as_deref();
+    }
+
+    /// Returns the directory that included files must be located in.
+    ///
+    /// # Returns
+    ///
+    /// The configured directory, else the directory of the input file, else the current working
+    /// directory.
+    ///
+    pub fn include_root(&self) -> PathBuf {
+        if let Some(root) = &self.include_root {
+            return root.clone();
+        }
+        return self
+            .input_file()
+            .and_then(Path::parent)
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_set_max_expression_length() {
+        let mut settings = Settings::default();
+        assert_eq!(
+            settings.max_expression_length(),
+            DEFAULT_MAX_EXPRESSION_LENGTH
+        );
+
+        let args = [String::from("100")];
+        let result = settings.set(
+            Setting::MaxExpressionLength,
+            "--max-expression-length",
+            None,
+            &mut args.iter(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(settings.max_expression_length(), 100);
+    }
+
+    #[test]
+    fn test_settings_set_invalid_value_error() {
+        let mut settings = Settings::default();
+        let result = settings.set(
+            Setting::MaxExpressionLength,
+            "--max-expression-length",
+            Some("-1"),
+            &mut [].iter(),
+        );
+        assert_eq!(
+            result,
+            Err(ParseArgsError::InvalidValue(
+                "--max-expression-length".to_string(),
+                "-1".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_settings_set_directive_prefix() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.directive_prefix(), DEFAULT_DIRECTIVE_PREFIX);
+
+        let result = settings.set(
+            Setting::DirectivePrefix,
+            "--directive-prefix",
+            Some("@@"),
+            &mut [].iter(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(settings.directive_prefix(), "@@");
+
+        for value in ["", "# "] {
+            let result = settings.set(
+                Setting::DirectivePrefix,
+                "--directive-prefix",
+                Some(value),
+                &mut [].iter(),
+            );
+            assert_eq!(
+                result,
+                Err(ParseArgsError::InvalidValue(
+                    "--directive-prefix".to_string(),
+                    value.to_string()
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_settings_include_root() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.include_root(), PathBuf::from("."));
+
+        settings.set_input_file("template.txt");
+        assert_eq!(settings.include_root(), PathBuf::from("."));
+
+        settings.set_input_file("templates/app/config.tmpl");
+        assert_eq!(
+            settings.input_file(),
+            Some(Path::new("templates/app/config.tmpl"))
+        );
+        assert_eq!(settings.include_root(), PathBuf::from("templates/app"));
+
+        let args = [String::from("templates")];
+        let result = settings.set(
+            Setting::IncludeRoot,
+            "--include-root",
+            None,
+            &mut args.iter(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(settings.include_root(), PathBuf::from("templates"));
+    }
+
+    #[test]
+    fn test_settings_set_dialect() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.dialect(), Dialect::Shell);
+
+        let result = settings.set(
+            Setting::Dialect,
+            "--dialect",
+            Some("autoconf"),
+            &mut [].iter(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(settings.dialect(), Dialect::Autoconf);
+        assert_eq!(settings.dialect().delimiters(), Some(("@", "@")));
+
+        let result = settings.set(Setting::Dialect, "--dialect", Some("jinja"), &mut [].iter());
+        assert_eq!(
+            result,
+            Err(ParseArgsError::InvalidValue(
+                "--dialect".to_string(),
+                "jinja".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_settings_set_variable_sources() {
+        let mut settings = Settings::default();
+        assert!(settings.variable_sources().is_empty());
+
+        for (setting, arg, value) in [
+            (Setting::EnvFile, "--env-file", ".env"),
+            (Setting::EnvDir, "--env-dir", "/run/secrets"),
+            (Setting::EnvFile, "--env-file", ".env.local"),
+        ] {
+            let result = settings.set(setting, arg, Some(value), &mut [].iter());
+            assert!(result.is_ok());
+        }
+        assert_eq!(
+            settings.variable_sources(),
+            [
+                VariableSource::EnvFile(PathBuf::from(".env")),
+                VariableSource::EnvDir(PathBuf::from("/run/secrets")),
+                VariableSource::EnvFile(PathBuf::from(".env.local")),
+            ]
+        );
+
+        let result = settings.set(Setting::EnvFile, "--env-file", Some(""), &mut [].iter());
+        assert_eq!(
+            result,
+            Err(ParseArgsError::InvalidValue(
+                "--env-file".to_string(),
+                String::new()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_settings_set_missing_value_error() {
+        let mut settings = Settings::default();
+        let args = [String::from("--color")];
+        let result = settings.set(
+            Setting::MaxExpressionLength,
+            "--max-expression-length",
+            None,
+            &mut args.iter(),
+        );
+        assert_eq!(
+            result,
+            Err(ParseArgsError::MissingValue(
+                "--max-expression-length".to_string()
+            ))
+        );
+    }
+}
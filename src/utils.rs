@@ -29,7 +29,7 @@ pub fn colorize_text(colored: bool, text: String, color: Color) -> String {
 /// # Returns
 ///
 /// * Result<String, String> - A Result containing either the modified result as a String on success
-/// or an error message as a String on failure.
+///   or an error message as a String on failure.
 ///
 /// # Errors
 ///
@@ -114,6 +114,17 @@ pub const START_PARAMETERS: &[&str] = &[
     "--variable",
     "-c",
     "--color",
+    "--directives",
+    "--relaxed-names",
+    "--prefer-env",
+    "--keep-trailing-newline",
+    "--multiline-expressions",
+    "--max-expression-length",
+    "--directive-prefix",
+    "--include-root",
+    "--dialect",
+    "--env-file",
+    "--env-dir",
 ];
 
 #[cfg(test)]
@@ -122,6 +133,9 @@ mod tests {
 
     #[test]
     fn test_colorize_text() {
+        // Force colors, as the test output is usually not a terminal
+        colored::control::set_override(true);
+
         // Test default behavior
         let text = "This is a test.";
         let result = colorize_text(false, text.to_string(), Color::Green);
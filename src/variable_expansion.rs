@@ -1,11 +1,19 @@
+use crate::arithmetic;
+use crate::env_subst::expand_argument;
 use crate::filters::Filters;
 use crate::flags::{Flag, Flags};
+use crate::functions;
+use crate::glob::Pattern;
+use crate::quoting;
 use crate::utils::{colorize_text, handle_flags_on_result};
+use crate::variables::Variables;
 use colored::Color;
-use std::env;
 
 /// Processes pattern stripping operations on the given `value` string, based on the provided `op` character and the pattern in `operation_data`.
 ///
+/// The pattern is a shell glob pattern (see `Pattern`). If the pattern starts with the operation character again
+/// ('##' or '%%'), the longest match is removed instead of the shortest one.
+///
 /// # Arguments
 ///
 /// * `op` - The operation character: '#' for prefix stripping and '%' for suffix stripping.
@@ -36,27 +44,63 @@ fn process_pattern_stripping(
 
     match operation_data {
         Some(pattern) => {
+            // A doubled operation character ('##' or '%%') removes the longest match
+            let (pattern, longest) = match pattern.strip_prefix(op) {
+                Some(stripped) => (stripped, true),
+                None => (pattern, false),
+            };
+            let pattern = Pattern::new(pattern);
+            let chars: Vec<char> = value.chars().collect();
+
             let (new_value, color) = match op {
-                // If the operation is '#', remove the shortest matching prefix (if any) from the value
-                '#' if value.starts_with(pattern) => {
-                    (value.strip_prefix(pattern).unwrap_or(value), Color::Blue)
-                }
-                // If the operation is '%', remove the shortest matching suffix (if any) from the value
-                '%' if value.ends_with(pattern) => {
-                    (value.strip_suffix(pattern).unwrap_or(value), Color::Blue)
-                }
-                // This is the case where the value does not start nor end with the pattern
-                '%' | '#' => (value, Color::Red),
+                // If the operation is '#', remove the matching prefix (if any) from the value
+                '#' => match pattern.match_prefix(&chars, longest) {
+                    Some(end) => (chars[end..].iter().collect(), Color::Blue),
+                    None => (value.to_string(), Color::Red),
+                },
+                // If the operation is '%', remove the matching suffix (if any) from the value
+                '%' => match pattern.match_suffix(&chars, longest) {
+                    Some(start) => (chars[..start].iter().collect(), Color::Blue),
+                    None => (value.to_string(), Color::Red),
+                },
                 // If the operation is invalid, return an error
                 _ => return Err(format!("\"{inner_expr}\" - Invalid operation: '{op}'")),
             };
 
-            return Ok(colorize_text(colored, new_value.to_string(), color));
+            return Ok(colorize_text(colored, new_value, color));
         }
         None => return Ok(value.to_string()),
     }
 }
 
+/// Renders the replacement string of a pattern replacement for a single match.
+///
+/// An unescaped `&` is replaced with the matched text. A backslash escapes the following character,
+/// e.g. `\&` results in a literal `&` and `\/` in a literal `/`.
+///
+/// # Arguments
+/// * `replacement` - The raw replacement string, as written in the expression
+/// * `matched` - The text matched by the pattern
+fn render_replacement(replacement: &str, matched: &str) -> String {
+    let mut rendered = String::with_capacity(replacement.len());
+    let mut escape_next = false;
+
+    for c in replacement.chars() {
+        if escape_next {
+            rendered.push(c);
+            escape_next = false;
+        } else if c == '\\' {
+            escape_next = true;
+        } else if c == '&' {
+            rendered.push_str(matched);
+        } else {
+            rendered.push(c);
+        }
+    }
+
+    return rendered;
+}
+
 /// Process pattern replacement in a string according to the specified rules.
 ///
 /// This function takes a string `value` and an optional `operation_data` and performs
@@ -70,6 +114,9 @@ fn process_pattern_stripping(
 /// - ${var/#pattern/replacement} - Replace the `pattern` with the `replacement` if it occurs at the start of the string
 /// - ${var/%pattern/replacement} - Replace the `pattern` with the `replacement` if it occurs at the end of the string
 ///
+/// The `pattern` is a shell glob pattern (see `Pattern`), which matches the longest possible text.
+/// An unescaped `&` in the `replacement` refers to the matched text.
+///
 /// # Arguments
 /// * `value` - The input string to perform the pattern replacement on
 /// * `operation_data` - An optional string containing the operation type and replacement data
@@ -92,8 +139,9 @@ fn process_pattern_replacement(
     match operation_data {
         Some(replace_data) => {
             // Determine the operation type and update replace_data accordingly
+            // '${VAR//pattern/replacement}' is passed without the operation character as "/pattern/replacement"
             let (operation_type, replace_data) =
-                if let Some(stripped_data) = replace_data.strip_prefix("//") {
+                if let Some(stripped_data) = replace_data.strip_prefix('/') {
                     ('/', stripped_data)
                 } else if let Some(stripped_data) = replace_data.strip_prefix('#') {
                     ('#', stripped_data)
@@ -103,20 +151,37 @@ fn process_pattern_replacement(
                     (' ', replace_data)
                 };
 
-            // Extract pattern and replacement, handling escaped slashes
+            // Split pattern and replacement at the first unescaped slash.
+            // Escapes are kept, they are resolved by the glob pattern and `render_replacement`.
             let mut pattern = String::new();
             let mut replacement = String::new();
-            let mut current = &mut pattern;
+            let mut in_replacement = false;
             let mut escape_next = false;
 
+            // Like in bash, a slash right after '//' belongs to the pattern, e.g. '${VAR////_}'
+            // replaces all '/' with '_'
+            let replace_data = match replace_data.strip_prefix('/') {
+                Some(rest) if operation_type == '/' => {
+                    pattern.push('/');
+                    rest
+                }
+                _ => replace_data,
+            };
+
             for c in replace_data.chars() {
+                let current = if in_replacement {
+                    &mut replacement
+                } else {
+                    &mut pattern
+                };
                 if escape_next {
                     current.push(c);
                     escape_next = false;
                 } else if c == '\\' {
+                    current.push(c);
                     escape_next = true;
-                } else if c == '/' {
-                    current = &mut replacement;
+                } else if c == '/' && !in_replacement {
+                    in_replacement = true;
                 } else {
                     current.push(c);
                 }
@@ -126,22 +191,40 @@ fn process_pattern_replacement(
                 return Err(format!("\"{inner_expr}\" - Trailing backslash"));
             }
 
+            let pattern = Pattern::new(&pattern);
+            let chars: Vec<char> = value.chars().collect();
+
+            // Returns the end of the longest non-empty match starting at `start`
+            let match_at = |start: usize| -> Option<usize> {
+                pattern
+                    .match_prefix(&chars[start..], true)
+                    .filter(|&len| len > 0)
+                    .map(|len| start + len)
+            };
+
             // Perform the replacement operation based on the operation type
             let (new_value, color) = match operation_type {
-                ' ' => {
-                    // Search pattern in value
-                    let tmp_value = value.replacen(&pattern, &replacement, 1);
-                    let color = if tmp_value == value {
-                        Color::Red
-                    } else {
-                        Color::Blue
-                    };
+                ' ' | '/' => {
+                    // Replace the first (' ') or all ('/') matches
+                    let mut tmp_value = String::with_capacity(value.len());
+                    let mut replaced = false;
+                    let mut i = 0;
+
+                    while i < chars.len() {
+                        match match_at(i) {
+                            Some(end) if operation_type == '/' || !replaced => {
+                                let matched: String = chars[i..end].iter().collect();
+                                tmp_value.push_str(&render_replacement(&replacement, &matched));
+                                replaced = true;
+                                i = end;
+                            }
+                            _ => {
+                                tmp_value.push(chars[i]);
+                                i += 1;
+                            }
+                        }
+                    }
 
-                    (tmp_value, color)
-                }
-                '/' => {
-                    // Replace all matches
-                    let tmp_value = value.replace(&pattern, &replacement);
                     let color = if tmp_value == value {
                         Color::Red
                     } else {
@@ -152,20 +235,30 @@ fn process_pattern_replacement(
                 }
                 '#' => {
                     // Replace match at the beginning
-                    if value.starts_with(&pattern) {
-                        (value.replacen(&pattern, &replacement, 1), Color::Blue)
-                    } else {
-                        (value.to_string(), Color::Red)
+                    match pattern.match_prefix(&chars, true) {
+                        Some(end) => {
+                            let matched: String = chars[..end].iter().collect();
+                            let rest: String = chars[end..].iter().collect();
+                            (
+                                render_replacement(&replacement, &matched) + &rest,
+                                Color::Blue,
+                            )
+                        }
+                        None => (value.to_string(), Color::Red),
                     }
                 }
                 '%' => {
                     // Replace match at the end
-                    if value.ends_with(&pattern) {
-                        let end_index = value.rfind(&pattern).unwrap();
-                        let (start, _) = value.split_at(end_index);
-                        (start.to_owned() + &replacement, Color::Blue)
-                    } else {
-                        (value.to_string(), Color::Red)
+                    match pattern.match_suffix(&chars, true) {
+                        Some(start) => {
+                            let rest: String = chars[..start].iter().collect();
+                            let matched: String = chars[start..].iter().collect();
+                            (
+                                rest + &render_replacement(&replacement, &matched),
+                                Color::Blue,
+                            )
+                        }
+                        None => (value.to_string(), Color::Red),
                     }
                 }
                 // Catch any other cases to satisfy the compiler, although they should never be reached
@@ -179,11 +272,25 @@ fn process_pattern_replacement(
     }
 }
 
+/// Changes an uppercase character to lowercase and a lowercase character to uppercase.
+fn toggle_case(c: char) -> String {
+    if c.is_uppercase() {
+        return c.to_lowercase().collect();
+    }
+    return c.to_uppercase().collect();
+}
+
 /// Processes case conversion operations on the given `value` string, based on the provided operation character and `operation_data`.
 ///
+/// A single operator converts the first character, a doubled operator all characters, e.g.
+/// `${VAR^}` and `${VAR^^}`. A glob pattern can follow the operator, e.g. `${VAR^^[aeiou]}`, in
+/// which case only characters matching the pattern are converted. The conversion works on
+/// Unicode scalar values and uses the Unicode case mappings, so a character can be converted to
+/// more than one character, e.g. 'ß' to "SS".
+///
 /// # Arguments
 ///
-/// * `op` - The operation character, either ',' or '^'.
+/// * `op` - The operation character: ',' for lowercase, '^' for uppercase or '~' to toggle the case.
 /// * `value` - The input string on which the operation is performed.
 /// * `operation_data` - Additional operation data wrapped in an `Option`. If `None`, the original value is returned.
 /// * `inner_expr` - The inner expression string to use in error messages.
@@ -205,57 +312,120 @@ fn process_case_conversion(
         return Ok(value.to_string());
     }
 
-    let (new_value, color) = match (op, operation_data) {
-        // If operation_data is None, return the original value
-        (_, None) => (value.to_string(), Color::Magenta),
-        // If operation_data is an empty string, convert the first character
-        (',', Some("")) => {
-            let lower = value[..1].to_ascii_lowercase();
-            let color = if lower == value[..1] {
-                Color::Red
-            } else {
-                Color::Blue
-            };
-            (lower + &value[1..], color)
-        }
-        ('^', Some("")) => {
-            let upper = value[..1].to_ascii_uppercase();
-            let color = if upper == value[..1] {
-                Color::Red
-            } else {
-                Color::Blue
-            };
-            (upper + &value[1..], color)
-        }
-        // If conversion is ',', convert the value to lowercase
-        (',', Some(",")) => {
-            let lower = value.to_lowercase();
-            let color = if lower == value {
-                Color::Red
-            } else {
-                Color::Blue
-            };
-            (lower, color)
-        }
-        // If conversion is '^', convert the value to uppercase
-        ('^', Some("^")) => {
-            let upper = value.to_uppercase();
-            let color = if upper == value {
-                Color::Red
-            } else {
-                Color::Blue
-            };
-            (upper, color)
-        }
+    // If operation_data is None, return the original value
+    let Some(data) = operation_data else {
+        return Ok(colorize_text(colored, value.to_string(), Color::Magenta));
+    };
+
+    let convert: fn(char) -> String = match op {
+        ',' => |c| c.to_lowercase().collect(),
+        '^' => |c| c.to_uppercase().collect(),
+        '~' => toggle_case,
         // If the operation is invalid, return an error
         _ => return Err(format!("\"{inner_expr}\" - Invalid operation: '{op}'")),
     };
 
+    // A doubled operator converts all characters, otherwise only the first character
+    let (all, pattern) = match data.strip_prefix(op) {
+        Some(pattern) => (true, pattern),
+        None => (false, data),
+    };
+    // Without a pattern, every character is converted
+    let pattern = (!pattern.is_empty()).then(|| Pattern::new(pattern));
+
+    let mut new_value = String::with_capacity(value.len());
+    for (index, c) in value.chars().enumerate() {
+        if (all || index == 0) && pattern.as_ref().is_none_or(|p| p.matches(&[c])) {
+            new_value.push_str(&convert(c));
+        } else {
+            new_value.push(c);
+        }
+    }
+
+    let color = if new_value == value {
+        Color::Red
+    } else {
+        Color::Blue
+    };
+
+    return Ok(colorize_text(colored, new_value, color));
+}
+
+/// Processes the transformation operations `${VAR@Q}`, `${VAR@U}`, `${VAR@u}`, `${VAR@L}` and
+/// `${VAR@E}`.
+///
+/// # Arguments
+///
+/// * `value` - The value of the variable, or `None` if it is not set.
+/// * `operation_data` - The transformation character, e.g. `Q`.
+/// * `inner_expr` - The inner expression string to use in error messages.
+/// * `colored` - Whether the output string should be colorized.
+///
+/// # Returns
+///
+/// * `Ok(String)` - The transformed value, or an empty string if the variable is not set.
+/// * `Err(String)` - An error string in the case of an unknown transformation: "{`inner_expr`} - Invalid transformation: '{`operation_data`}'"
+fn process_transformation(
+    value: Option<&str>,
+    operation_data: Option<&str>,
+    inner_expr: &str,
+    colored: bool,
+) -> Result<String, String> {
+    let transform: fn(&str) -> String = match operation_data.unwrap_or_default() {
+        // Quote the value for re-use as shell input
+        "Q" => quoting::shell,
+        "U" => str::to_uppercase,
+        "L" => str::to_lowercase,
+        // Change the first character to uppercase
+        "u" => |value| {
+            let mut chars = value.chars();
+            return chars.next().map_or_else(String::new, |first| {
+                return first.to_uppercase().chain(chars).collect();
+            });
+        },
+        // Expand backslash escape sequences like in $'...'
+        "E" => quoting::expand_escapes,
+        data => {
+            return Err(format!(
+                "\"{inner_expr}\" - Invalid transformation: '{data}'"
+            ))
+        }
+    };
+
+    // Like in bash, an unset variable is not transformed, e.g. '${UNSET@Q}' is empty
+    let Some(value) = value else {
+        return Ok(String::new());
+    };
+
+    let new_value = transform(value);
+    let color = if new_value == value {
+        Color::Red
+    } else {
+        Color::Blue
+    };
+
     return Ok(colorize_text(colored, new_value, color));
 }
 
+/// Parses an offset or length of a substring extraction as a signed integer.
+///
+/// Surrounding whitespace and parentheses are ignored, so that negative values can be written
+/// as `${VAR: -4}` or `${VAR:(-4)}`, like in bash.
+fn parse_substring_number(number: &str) -> Option<i64> {
+    let number = number.trim();
+    let number = number
+        .strip_prefix('(')
+        .and_then(|n| n.strip_suffix(')'))
+        .unwrap_or(number);
+    return number.trim().parse::<i64>().ok();
+}
+
 /// Processes substring extraction operations on the given `value` string, based on the provided `operation_data`.
 ///
+/// Offset and length are counted in characters. A negative offset counts from the end of the value,
+/// a negative length specifies the end as an offset from the end of the value. An offset beyond the
+/// end of the value results in an empty string.
+///
 /// # Arguments
 ///
 /// * `value` - The input string on which the operation is performed.
@@ -266,7 +436,8 @@ fn process_case_conversion(
 /// # Returns
 ///
 /// * `Ok(String)` - The extracted substring from the input value, colorized if `colored` is true.
-/// * `Err(String)` - An error string in the case of an invalid start offset or length.
+/// * `Err(String)` - An error string in the case of an invalid start offset or length, or if a negative length
+///   results in an end before the start offset.
 fn process_substring_extraction(
     value: &str,
     operation_data: Option<&String>,
@@ -279,313 +450,2164 @@ fn process_substring_extraction(
     }
 
     // If operation_data is None, return the original value
-    if operation_data.is_none() {
+    let Some(operation_data) = operation_data else {
         return Ok(value.to_string());
-    }
-
-    let operation_data = operation_data.unwrap();
+    };
 
     // Split operation_data using ':' to get start and len parts
     let mut parts = operation_data.splitn(2, ':');
 
-    // Parse the first part (start) as a usize
+    // Parse the first part (start) as a signed number
     let start = parts
         .next()
-        .ok_or(format!("\"${{{inner_expr}}}\" - invalid start offset"))?
-        .parse::<usize>()
-        .map_err(|_| format!("\"${{{inner_expr}}}\" - invalid start offset"))?;
+        .and_then(parse_substring_number)
+        .ok_or(format!("\"${{{inner_expr}}}\" - invalid start offset"))?;
+
+    // Parse the second part (len) as an optional signed number, an empty length is zero
+    let len = match parts.next() {
+        None => None,
+        Some(len) if len.trim().is_empty() => Some(0),
+        Some(len) => Some(
+            parse_substring_number(len).ok_or(format!("\"${{{inner_expr}}}\" - Invalid length"))?,
+        ),
+    };
 
-    // Parse the second part (len) as an optional usize
-    let len = parts
-        .next()
-        .map(str::parse)
-        .transpose()
-        .map_err(|_| format!("\"${{{inner_expr}}}\" - Invalid length"))?;
+    let chars: Vec<char> = value.chars().collect();
+    let char_count = i64::try_from(chars.len()).unwrap_or(i64::MAX);
+
+    // A negative offset counts from the end, clamp it to the bounds of the value
+    let start = if start < 0 {
+        char_count.saturating_add(start)
+    } else {
+        start
+    };
+    if start < 0 || start >= char_count {
+        return Ok(colorize_text(colored, String::new(), Color::Blue));
+    }
+
+    // A negative length is an offset from the end
+    let end = match len {
+        None => char_count,
+        Some(len) if len < 0 => {
+            let end = char_count.saturating_add(len);
+            if end < start {
+                return Err(format!(
+                    "\"${{{inner_expr}}}\" - Invalid length: end is before the start offset"
+                ));
+            }
+            end
+        }
+        Some(len) => start.saturating_add(len).min(char_count),
+    };
 
-    // Extract the substring from value, skipping 'start' characters and taking 'len' characters
+    // Both bounds are within 0..=char_count at this point
+    let start = usize::try_from(start).unwrap_or_default();
+    let end = usize::try_from(end).unwrap_or_default();
+
+    // Extract the substring from value
     return Ok(colorize_text(
         colored,
-        value
-            .chars()
-            .skip(start)
-            .take(len.unwrap_or(value.len() - start))
-            .collect(),
+        chars[start..end].iter().collect(),
         Color::Blue,
     ));
 }
 
-/// Processes the inner expression of a variable, applying the specified operations and flags.
+/// Processes default value operations on the given `value`, based on the provided `op` character.
 ///
 /// # Arguments
 ///
-/// * `inner_expr` - The inner expression string to process.
-/// * `flags` - Flags that affect how the inner expression is processed.
+/// * `op` - The operation character: ':' to use the default if the variable is unset or empty, '-' to use it only if the variable is unset.
+///   The same applies to the assignment operations ':=' (':') and '=' ('-').
+/// * `value` - The value of the variable, `None` if the variable is not set.
+/// * `default_value` - The default value to use.
+/// * `colored` - A boolean flag that determines whether the output string should be colorized.
+///
+/// # Returns
+///
+/// * `String` - The value of the variable (green) or the default value (yellow).
+fn process_default_value(
+    op: char,
+    value: Option<String>,
+    default_value: &str,
+    colored: bool,
+) -> String {
+    match value {
+        // ${VAR:-default} also applies the default if the variable is set but empty
+        Some(value) if op == ':' && value.is_empty() => {
+            colorize_text(colored, default_value.to_string(), Color::Yellow)
+        }
+        Some(value) => colorize_text(colored, value, Color::Green),
+        None => colorize_text(colored, default_value.to_string(), Color::Yellow),
+    }
+}
+
+/// Processes alternate value operations on the given `value`, based on the provided `op` character.
+///
+/// # Arguments
+///
+/// * `op` - The operation character: ':' to use the alternate value if the variable is set and not empty, '+' to use it if the variable is set.
+/// * `value` - The value of the variable, `None` if the variable is not set.
+/// * `alternate_value` - The alternate value to use.
+/// * `colored` - A boolean flag that determines whether the output string should be colorized.
+///
+/// # Returns
+///
+/// * `String` - The alternate value (blue) or an empty string.
+fn process_alternate_value(
+    op: char,
+    value: Option<&str>,
+    alternate_value: &str,
+    colored: bool,
+) -> String {
+    match value {
+        // ${VAR:+alternate} treats an empty variable like an unset one
+        Some("") if op == ':' => String::new(),
+        Some(_) => colorize_text(colored, alternate_value.to_string(), Color::Blue),
+        None => String::new(),
+    }
+}
+
+/// Processes required value operations on the given `value`, based on the provided `op` character.
+///
+/// # Arguments
+///
+/// * `op` - The operation character: ':' to fail if the variable is unset or empty, '?' to fail only if the variable is unset.
+/// * `value` - The value of the variable, `None` if the variable is not set.
+/// * `message` - The error message to use. If empty, a default message is used.
+/// * `var_name` - The name of the variable, used in the error message.
+/// * `colored` - A boolean flag that determines whether the output string should be colorized.
+///
+/// # Returns
+///
+/// * `Ok(String)` - The value of the variable, colorized if `colored` is true.
+/// * `Err(String)` - An error string if the variable is unset, or empty in the case of ':'.
+fn process_required_value(
+    op: char,
+    value: Option<String>,
+    message: &str,
+    var_name: &str,
+    colored: bool,
+) -> Result<String, String> {
+    match value {
+        // ${VAR:?message} also fails if the variable is set but empty
+        Some(value) if op == ':' && value.is_empty() => {
+            if message.is_empty() {
+                return Err(format!("environment variable '{var_name}' is empty"));
+            }
+            return Err(format!("environment variable '{var_name}': {message}"));
+        }
+        Some(value) => return Ok(colorize_text(colored, value, Color::Green)),
+        None => {
+            if message.is_empty() {
+                return Err(format!("environment variable '{var_name}' is not set"));
+            }
+            return Err(format!("environment variable '{var_name}': {message}"));
+        }
+    }
+}
+
+/// Checks whether `name` is a valid variable name.
+///
+/// Valid variable names start with a letter or underscore and can be followed by any combination
+/// of letters, numbers, or underscores.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    return chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+}
+
+/// Checks whether `c` can be part of a variable name.
+fn is_name_char(c: char) -> bool {
+    return c.is_ascii_alphanumeric() || c == '_';
+}
+
+/// Returns the length of the variable name at the start of `text`.
+///
+/// A name consists of letters, digits and underscores. With `relaxed` names, a dot between two of
+/// these characters is part of the name as well, e.g. `db.host`. A dash is only part of a name
+/// that already contains a dot, e.g. `server.max-size`, so that `VAR-default` is still the
+/// variable `VAR` with a default value.
+///
+/// # Arguments
+///
+/// * `text` - The text starting with the name.
+/// * `relaxed` - Whether dots and dashes are allowed in the name.
+///
+/// # Returns
+///
+/// The length of the name in bytes, `0` if `text` does not start with a name character.
+///
+pub fn name_length(text: &str, relaxed: bool) -> usize {
+    let mut chars = text.char_indices().peekable();
+    let mut previous_is_name_char = false;
+    let mut has_dot = false;
+
+    while let Some((index, c)) = chars.next() {
+        let is_separator = relaxed
+            && (c == '.' || (c == '-' && has_dot))
+            && previous_is_name_char
+            && chars.peek().is_some_and(|(_, next)| is_name_char(*next));
+        if !is_name_char(c) && !is_separator {
+            return index;
+        }
+        previous_is_name_char = !is_separator;
+        has_dot |= c == '.';
+    }
+
+    return text.len();
+}
+
+/// Returns the name under which the variable `name` is looked up.
+///
+/// With relaxed names (`--relaxed-names`), dots and dashes are replaced by underscores and the
+/// name is changed to uppercase, e.g. `DB_HOST` for `db.host`. Otherwise `name` is returned as is.
+///
+/// # Arguments
+///
+/// * `name` - The name as written in the expression.
+/// * `flags` - Flags that affect how the variable is processed.
+///
+pub fn lookup_name(name: &str, flags: &Flags) -> String {
+    if !flags.is_flag_set(Flag::RelaxedNames) {
+        return name.to_string();
+    }
+    return name.replace(['.', '-'], "_").to_ascii_uppercase();
+}
+
+/// Checks whether `name` is a valid relaxed variable name, e.g. `db.host`.
+///
+/// Relaxed names start like valid variable names and can contain dots and dashes, see
+/// `name_length`.
+fn is_relaxed_name(name: &str) -> bool {
+    return name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name_length(name, true) == name.len();
+}
+
+/// Processes the length operation `${#VAR}`, which returns the number of characters of the value.
+///
+/// # Arguments
+///
+/// * `var_name` - The name of the variable.
+/// * `flags` - Flags that affect how the variable is processed.
 /// * `filters` - Filters that determine which variables should be processed.
+/// * `variables` - The variables of the current render.
 ///
 /// # Returns
 ///
-/// * `Ok(String)` - The processed inner expression, taking into account the specified operations and flags.
-/// * `Err(String)` - An error string in the case of an invalid character, operation, or flag handling error.
-pub fn process_inner_expression(
-    inner_expr: &str,
+/// * `Ok(Expansion)` - The number of characters of the value, or `Kept` if the variable is filtered
+///   out or not replaced because of the `NoReplace` flags.
+/// * `Err(String)` - An error string if the Fail flags apply to the variable.
+fn process_length(
+    var_name: &str,
     flags: &Flags,
     filters: &Filters,
-) -> Result<String, String> {
-    let mut iter = inner_expr.chars().peekable();
+    variables: &Variables,
+) -> Result<Expansion, String> {
+    let colored = flags.is_flag_set(Flag::Color);
+    let original_variable = format!("${{#{var_name}}}");
 
-    let mut var_name: String = String::new();
-    let mut operation: Option<char> = None;
-    let mut operation_data: Option<String> = None;
+    // Check if the variable name matches any filters
+    if filters.matches(var_name) == Some(false) {
+        return Ok(Expansion::Kept(original_variable, Color::Magenta));
+    }
+
+    // The Fail and NoReplace flags apply to the variable itself, not to its length
+    let value = variables
+        .get(&lookup_name(var_name, flags))
+        .unwrap_or_default();
+    if let Expansion::Kept(original, color) =
+        check_result(value.clone(), var_name, original_variable, flags)?
+    {
+        return Ok(Expansion::Kept(original, color));
+    }
+
+    return Ok(Expansion::Value(colorize_text(
+        colored,
+        value.chars().count().to_string(),
+        Color::Blue,
+    )));
+}
+
+/// Processes the indirect expansions `${!REF}`, which uses the value of the variable named by the
+/// value of `REF`, and `${!PREFIX*}` / `${!PREFIX@}`, which list the names of all variables
+/// starting with `PREFIX`.
+///
+/// # Arguments
+///
+/// * `expr` - The inner expression without the leading '!', e.g. `REF:-default` or `PREFIX*`.
+/// * `flags` - Flags that affect how the variable is processed.
+/// * `filters` - Filters that determine which variables should be processed.
+/// * `variables` - The variables of the current render.
+///
+/// # Returns
+///
+/// * `Ok(Expansion)` - The expanded value of the referenced variable, or the sorted names of the
+///   matching variables separated by spaces.
+/// * `Err(String)` - An error string if `REF` is not set or does not contain a valid variable name.
+fn process_indirection(
+    expr: &str,
+    flags: &Flags,
+    filters: &Filters,
+    variables: &mut Variables,
+) -> Result<Expansion, String> {
     let colored = flags.is_flag_set(Flag::Color);
 
-    // Iterate through the characters of the inner expression
-    while let Some(c) = iter.next() {
-        if c.is_ascii_alphanumeric() || c == '_' {
-            // If the character is alphanumeric or '_', add it to the var_name
-            var_name.push(c);
-            continue;
-        }
+    // Process ${!PREFIX*} and ${!PREFIX@}, names excluded by the filters are not listed
+    if let Some(prefix) = expr
+        .strip_suffix(['*', '@'])
+        .filter(|prefix| is_valid_name(prefix))
+    {
+        let names: Vec<String> = variables
+            .names()
+            .into_iter()
+            .filter(|name| name.starts_with(prefix) && filters.matches(name) != Some(false))
+            .collect();
+        return Ok(Expansion::Value(colorize_text(
+            colored,
+            names.join(" "),
+            Color::Green,
+        )));
+    }
 
-        if c == '#' || c == '%' || c == '/' || c == ',' || c == ':' || c == '=' || c == '^' {
-            // If an operation hasn't been found yet, and the current character is a valid operation, set the operation
-            operation = Some(c);
-            let mut data = String::new();
-            for c in iter.by_ref() {
-                data.push(c);
-            }
-            operation_data = Some(data);
-            break;
-        }
-        return Err(format!("Invalid character in expression: {c}"));
+    // With relaxed names, both the reference and the name it contains can be relaxed names,
+    // e.g. ${!db.ref} with 'db.host'
+    let relaxed = flags.is_flag_set(Flag::RelaxedNames);
+    let is_name = |name: &str| return is_valid_name(name) || (relaxed && is_relaxed_name(name));
+    let (ref_name, operation) = expr.split_at(name_length(expr, relaxed));
+    if !is_name(ref_name) {
+        return Err(format!("Invalid indirect expansion: ${{!{expr}}}"));
     }
 
-    // Get the environment variable value for the given var_name
-    let value = env::var(&var_name).unwrap_or_default();
+    // Check if the name of the reference matches any filters
+    if filters.matches(ref_name) == Some(false) {
+        let original_variable = format!("${{!{ref_name}}}");
+        return Ok(Expansion::Kept(original_variable, Color::Magenta));
+    }
 
-    // Perform the specified operation, if any, on the value
-    let result = if let Some(op) = operation {
-        match op {
-            // Process '#' and '%' operations for pattern stripping
-            '#' | '%' => process_pattern_stripping(
-                op,
-                &value,
-                operation_data.as_deref(),
-                inner_expr,
-                colored,
-            ),
-            // Process '/' operation for pattern replacement
-            '/' => {
-                process_pattern_replacement(&value, operation_data.as_deref(), inner_expr, colored)
-            }
-            // Process ',' and '^' operations for case conversion
-            ',' | '^' => {
-                process_case_conversion(op, &value, operation_data.as_deref(), inner_expr, colored)
+    let target = match variables.get(&lookup_name(ref_name, flags)) {
+        Some(target) if is_name(&target) => target,
+        Some(target) => {
+            return Err(format!(
+                "Invalid indirect expansion: '{target}' of variable '{ref_name}' is not a valid variable name"
+            ))
+        }
+        None => {
+            return Err(format!(
+                "Invalid indirect expansion: variable '{ref_name}' is not set"
+            ))
+        }
+    };
+
+    // Process the referenced variable with the remaining operation, e.g. ${TARGET:-default}
+    return expand_inner_expression(&format!("{target}{operation}"), flags, filters, variables);
+}
+
+/// Splits an inner expression into the segments of a pipeline like `VAR | lower | trim`.
+///
+/// Only unescaped pipes with whitespace on both sides and outside of nested expressions split
+/// the expression. If the first segment has an operator, e.g. a default value, every following
+/// segment must start with the name of a registered function. So e.g. `${SEPARATOR:-|}`,
+/// `${FORMATS:-json|yaml}` and `${MODE:-read | write}` are not pipelines, while
+/// `${HOST | lowercase}` fails with an unknown function. An escaped pipe `\|` in a pipeline is a literal pipe.
+///
+/// # Returns
+///
+/// * `Option<Vec<String>>` - The trimmed segments, or `None` if the expression is not a pipeline.
+fn split_pipeline(inner_expr: &str) -> Option<Vec<String>> {
+    let mut segments = vec![String::new()];
+    let mut depth = 0;
+    let mut chars = inner_expr.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let segment = segments.last_mut()?;
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                segment.push('|');
+                chars.next();
+                continue;
             }
-            // Process ':' operation for default value or substring extraction
-            ':' => {
-                // check if next character is -
-                if operation_data.as_ref().unwrap().starts_with('-') {
-                    // extract default value
-                    let default_value = operation_data.as_ref().unwrap().get(1..).unwrap();
-                    // return default value if value is empty
-                    if value.is_empty() {
-                        Ok(colorize_text(
-                            colored,
-                            default_value.to_string(),
-                            Color::Yellow,
-                        ))
-                    } else {
-                        Ok(colorize_text(colored, value, Color::Green))
-                    }
-                } else {
-                    // otherwise, process substring extraction
-                    process_substring_extraction(
-                        &value,
-                        operation_data.as_ref(),
-                        inner_expr,
-                        colored,
-                    )
-                }
+            '{' if segment.ends_with('$') => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            '|' if depth == 0
+                && segment.ends_with(char::is_whitespace)
+                && chars.peek().is_some_and(|next| next.is_whitespace()) =>
+            {
+                segments.push(String::new());
+                continue;
             }
-            _ => return Err(format!("Invalid operation: {op}")),
+            _ => {}
         }
-    } else {
-        Ok(value)
-    };
+        segment.push(c);
+    }
+
+    if segments.len() == 1 {
+        return None;
+    }
+
+    let segments: Vec<String> = segments.iter().map(|s| s.trim().to_string()).collect();
+    let is_function = |segment: &String| {
+        return functions::is_function(segment.split(':').next().unwrap_or_default());
+    };
+    if !is_valid_name(&segments[0]) && !segments[1..].iter().all(is_function) {
+        return None;
+    }
+
+    return Some(segments);
+}
+
+/// Processes a pipeline like `${VAR | lower | trim}`, which applies the registered functions
+/// (see `functions.rs`) from left to right to the value of the first segment.
+///
+/// # Arguments
+///
+/// * `inner_expr` - The inner expression, used in error messages.
+/// * `segments` - The segments of the pipeline, see `split_pipeline`.
+/// * `flags` - Flags that affect how the variable is processed.
+/// * `filters` - Filters that determine which variables should be processed.
+/// * `variables` - The variables of the current render.
+///
+/// # Returns
+///
+/// * `Ok(Expansion)` - The transformed value (blue), or `Kept` if the variable is filtered out or
+///   not replaced because of the `NoReplace` flags.
+/// * `Err(String)` - An error string if the first segment is invalid, or if a function is unknown or fails.
+fn process_pipeline(
+    inner_expr: &str,
+    segments: &[String],
+    flags: &Flags,
+    filters: &Filters,
+    variables: &mut Variables,
+) -> Result<Expansion, String> {
+    let colored = flags.is_flag_set(Flag::Color);
+    let head = &segments[0];
+
+    // The variable name, e.g. 'VAR' for 'VAR:-default' or '#VAR'
+    let prefix_length = usize::from(head.starts_with(['#', '!']));
+    let key_length = prefix_length
+        + name_length(
+            &head[prefix_length..],
+            flags.is_flag_set(Flag::RelaxedNames),
+        );
+    let key = &head[..key_length];
+    let var_name = key.trim_start_matches(['#', '!']);
+    let original_variable = format!("${{{key}}}");
+
+    // Check if the variable name matches any filters
+    if filters.matches(var_name) == Some(false) {
+        return Ok(Expansion::Kept(original_variable, Color::Magenta));
+    }
+
+    // Evaluate the first segment without colors, the result is colorized as a whole
+    let mut plain_flags = flags.clone();
+    plain_flags.update(Flag::Color, false);
+    let mut value = match expand_inner_expression(head, &plain_flags, filters, variables)? {
+        Expansion::Value(value) => value,
+        // The variable is kept as is because of the NoReplace flags
+        Expansion::Kept(_, color) => return Ok(Expansion::Kept(original_variable, color)),
+    };
+
+    for segment in &segments[1..] {
+        let (name, argument) = match segment.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (segment.as_str(), None),
+        };
+        let argument = match argument {
+            Some(argument) => Some(expand_argument(argument, flags, filters, variables, false)?),
+            None => None,
+        };
+
+        value = functions::apply(name, &value, argument.as_deref())
+            .map_err(|e| format!("\"{inner_expr}\" - {e}"))?;
+    }
+
+    return Ok(Expansion::Value(colorize_text(colored, value, Color::Blue)));
+}
+
+/// Returns the variables that an arithmetic expression refers to, e.g. `PORT` for `PORT + 1`,
+/// `$PORT + 1` or `${PORT} + 1`.
+///
+/// # Arguments
+///
+/// * `expression` - The expression between `$((` and `))`.
+/// * `relaxed` - Whether braced variables can have relaxed names, e.g. `${db.port}`.
+///
+/// # Returns
+///
+/// * `Vec<(&str, bool)>` - The names of the variables and whether the reference has a value
+///   operator like `${PORT:-8080}`, which applies if the variable is unset or empty.
+fn arithmetic_references(expression: &str, relaxed: bool) -> Vec<(&str, bool)> {
+    let mut references = Vec::new();
+    let mut index = 0;
+
+    while let Some(c) = expression[index..].chars().next() {
+        let rest = &expression[index..];
+
+        if let Some(braced) = rest.strip_prefix("${") {
+            let braced = braced.trim_start_matches(['#', '!']);
+            let name = &braced[..name_length(braced, relaxed)];
+            // The words of nested expressions and default values are not part of the expression
+            let mut depth = 0;
+            let end = braced
+                .find(|c| {
+                    match c {
+                        '{' => depth += 1,
+                        '}' if depth == 0 => return true,
+                        '}' => depth -= 1,
+                        _ => {}
+                    }
+                    return false;
+                })
+                .unwrap_or(braced.len());
+            let operation = braced[name.len()..end].trim_start_matches(':');
+            references.push((name, operation.starts_with(['-', '=', '?', '+'])));
+            index = expression.len() - braced.len() + end;
+            continue;
+        }
+
+        // '$PORT' and 'PORT' refer to the same variable, numbers like '0x1f' are skipped
+        let word = rest.strip_prefix('$').unwrap_or(rest);
+        let length = name_length(word, false);
+        if word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            references.push((&word[..length], false));
+        }
+        index += rest.len() - word.len() + length.max(c.len_utf8());
+    }
+
+    return references;
+}
+
+/// Processes the arithmetic expansion `$((EXPRESSION))`.
+///
+/// Variables like `$PORT` or `${PORT:-8080}` are expanded first. Variable names without a dollar
+/// sign, like `PORT + 1`, must be set to an integer. See `arithmetic::evaluate` for the
+/// supported operators.
+///
+/// Like a variable, the expression is kept as is if a variable it refers to is excluded by the
+/// filters, or is not replaced because of the `NoReplace` flags.
+///
+/// # Arguments
+///
+/// * `expression` - The expression between `$((` and `))`.
+/// * `flags` - Flags that affect how the variables are processed.
+/// * `filters` - Filters that determine which variables can be used.
+/// * `variables` - The variables of the current render.
+///
+/// # Returns
+///
+/// * `Ok(String)` - The result of the expression (blue), or the original expression if a variable
+///   is excluded by the filters (magenta) or not replaced because of the `NoReplace` flags (red).
+/// * `Err(String)` - An error string if the expression is invalid, if a variable is not set or
+///   not an integer, or if a division by zero or an overflow occurs.
+pub fn process_arithmetic(
+    expression: &str,
+    flags: &Flags,
+    filters: &Filters,
+    variables: &mut Variables,
+) -> Result<String, String> {
+    let colored = flags.is_flag_set(Flag::Color);
+    let no_replace = flags.is_flag_set(Flag::NoReplace);
+    let no_replace_unset = no_replace || flags.is_flag_set(Flag::NoReplaceUnset);
+    let no_replace_empty = no_replace || flags.is_flag_set(Flag::NoReplaceEmpty);
+
+    let references = arithmetic_references(expression, flags.is_flag_set(Flag::RelaxedNames));
+    let kept = references.iter().find_map(|&(name, has_value_operator)| {
+        if filters.matches(name) == Some(false) {
+            return Some(Color::Magenta);
+        }
+        let missing = match variables.get(&lookup_name(name, flags)) {
+            None => no_replace_unset,
+            Some(value) => value.is_empty() && no_replace_empty,
+        };
+        return (missing && !has_value_operator).then_some(Color::Red);
+    });
+    if let Some(color) = kept {
+        return Ok(colorize_text(colored, format!("$(({expression}))"), color));
+    }
+
+    let expanded = expand_argument(expression, flags, filters, variables, false)?;
+
+    let result = arithmetic::evaluate(&expanded, &mut |name| {
+        if filters.matches(name) == Some(false) {
+            return Err(format!("Variable '{name}' is excluded by the filters"));
+        }
+        let value = variables
+            .get(&lookup_name(name, flags))
+            .ok_or_else(|| format!("Variable '{name}' is not set"))?;
+        return arithmetic::parse_number(value.trim())
+            .ok_or_else(|| format!("Variable '{name}' is not an integer: '{value}'"));
+    })
+    .map_err(|e| format!("\"$(({expression}))\" - {e}"))?;
+
+    return Ok(colorize_text(colored, result.to_string(), Color::Blue));
+}
+
+/// The result of an inner expression.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Expansion {
+    /// The expression is replaced by the value.
+    Value(String),
+    /// The expression is kept as is, because the variable is excluded by the filters or not
+    /// replaced because of the `NoReplace` flags. Contains the expression in the `${...}` syntax
+    /// and the color to highlight it with.
+    Kept(String, Color),
+}
+
+/// Applies the `Fail` and `NoReplace` flags to the `result` of the variable `var_name`, see
+/// `handle_flags_on_result`.
+///
+/// # Returns
+///
+/// * `Ok(Expansion)` - The result, or `Kept` with the `original_variable` if the `NoReplace` flags
+///   apply.
+/// * `Err(String)` - An error string if the Fail flags apply.
+fn check_result(
+    result: String,
+    var_name: &str,
+    original_variable: String,
+    flags: &Flags,
+) -> Result<Expansion, String> {
+    let checked = handle_flags_on_result(result.clone(), var_name, &original_variable, flags)?;
+    if checked != result {
+        return Ok(Expansion::Kept(original_variable, Color::Red));
+    }
+    return Ok(Expansion::Value(result));
+}
+
+/// Processes the inner expression of a variable, applying the specified operations and flags.
+///
+/// # Arguments
+///
+/// * `inner_expr` - The inner expression string to process.
+/// * `flags` - Flags that affect how the inner expression is processed.
+/// * `filters` - Filters that determine which variables should be processed.
+/// * `variables` - The variables of the current render. Assignments like `${VAR:=default}` are stored here.
+///
+/// # Returns
+///
+/// * `Ok(String)` - The processed inner expression, taking into account the specified operations and flags.
+///   Expressions that are kept as is are returned in the `${...}` syntax.
+/// * `Err(String)` - An error string in the case of an invalid character, operation, or flag handling error.
+pub fn process_inner_expression(
+    inner_expr: &str,
+    flags: &Flags,
+    filters: &Filters,
+    variables: &mut Variables,
+) -> Result<String, String> {
+    return match expand_inner_expression(inner_expr, flags, filters, variables)? {
+        Expansion::Value(value) => Ok(value),
+        Expansion::Kept(original, color) => Ok(colorize_text(
+            flags.is_flag_set(Flag::Color),
+            original,
+            color,
+        )),
+    };
+}
+
+/// Processes the inner expression of a variable like `process_inner_expression`, but tells
+/// expressions that are kept as is apart from values.
+///
+/// # Returns
+///
+/// * `Ok(Expansion)` - The value of the expression, or `Kept` if it is kept as is.
+/// * `Err(String)` - An error string in the case of an invalid character, operation, or flag handling error.
+pub fn expand_inner_expression(
+    inner_expr: &str,
+    flags: &Flags,
+    filters: &Filters,
+    variables: &mut Variables,
+) -> Result<Expansion, String> {
+    // Process pipelines like ${VAR | lower | trim}
+    if let Some(segments) = split_pipeline(inner_expr) {
+        return process_pipeline(inner_expr, &segments, flags, filters, variables);
+    }
+
+    let relaxed = flags.is_flag_set(Flag::RelaxedNames);
+
+    // Process ${#VAR}, which returns the length of the value
+    if let Some(var_name) = inner_expr.strip_prefix('#').filter(|name| {
+        return is_valid_name(name) || (relaxed && is_relaxed_name(name));
+    }) {
+        return process_length(var_name, flags, filters, variables);
+    }
+
+    // Process ${!REF}, ${!PREFIX*} and ${!PREFIX@}
+    if let Some(expr) = inner_expr.strip_prefix('!') {
+        return process_indirection(expr, flags, filters, variables);
+    }
+
+    let mut iter = inner_expr.chars().peekable();
+
+    let mut var_name: String = String::new();
+    let mut operation: Option<char> = None;
+    let mut operation_data: Option<String> = None;
+    let colored = flags.is_flag_set(Flag::Color);
+
+    // Iterate through the characters of the inner expression
+    while let Some(c) = iter.next() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            // If the character is alphanumeric or '_', add it to the var_name
+            var_name.push(c);
+            continue;
+        }
+
+        // With relaxed names, a dot between two name characters is part of the name, e.g.
+        // ${db.host}. A dash only after a dot, e.g. ${server.max-size}, see `name_length`
+        if relaxed
+            && (c == '.' || (c == '-' && var_name.contains('.')))
+            && !var_name.is_empty()
+            && iter.peek().is_some_and(|next| is_name_char(*next))
+        {
+            var_name.push(c);
+            continue;
+        }
+
+        if c == '#'
+            || c == '%'
+            || c == '/'
+            || c == ','
+            || c == ':'
+            || c == '-'
+            || c == '='
+            || c == '?'
+            || c == '+'
+            || c == '^'
+            || c == '~'
+            || c == '@'
+        {
+            // If an operation hasn't been found yet, and the current character is a valid operation, set the operation
+            operation = Some(c);
+            let mut data = String::new();
+            for c in iter.by_ref() {
+                data.push(c);
+            }
+            operation_data = Some(data);
+            break;
+        }
+
+        // ${VAR|upper} looks like a pipeline, but the separator needs spaces around it
+        let rest: String = iter.collect();
+        let function = rest
+            .trim_start_matches(|c: char| c == '|' || c.is_whitespace())
+            .split([':', '|', ' '])
+            .next()
+            .unwrap_or_default();
+        let is_separator = c == '|' || (c.is_whitespace() && rest.trim_start().starts_with('|'));
+        if is_separator && functions::is_function(function) {
+            return Err(format!(
+                "\"{inner_expr}\" - Pipeline separators need spaces around them, e.g. '{var_name} | {function}'"
+            ));
+        }
+        return Err(format!("Invalid character in expression: {c}"));
+    }
+
+    let original_variable = format!("${{{var_name}}}");
+
+    // Check if the variable name matches any filters
+    if filters.matches(&var_name) == Some(false) {
+        return Ok(Expansion::Kept(original_variable, Color::Magenta));
+    }
+
+    // Get the variable value for the given var_name, `None` if it is not set
+    let lookup_name = lookup_name(&var_name, flags);
+    let env_value = variables.get(&lookup_name);
+    let value = env_value.clone().unwrap_or_default();
+
+    // Split the default, assignment, required and alternate value operations into the operator
+    // and its argument, e.g. '-' and "default" for ${VAR:-default}. The ':' variants also treat
+    // an empty variable as missing.
+    let value_operation = match (operation, operation_data.as_deref()) {
+        (Some(op @ ('-' | '=' | '?' | '+')), Some(data)) => Some((op, op, data)),
+        (Some(':'), Some(data)) => data
+            .chars()
+            .next()
+            .filter(|c| matches!(c, '-' | '=' | '?' | '+'))
+            .map(|c| (':', c, &data[1..])),
+        _ => None,
+    };
+
+    if let Some((op, kind, argument)) = value_operation {
+        let missing = match env_value.as_deref() {
+            None => true,
+            Some(value) => op == ':' && value.is_empty(),
+        };
+
+        // The argument is only expanded if it is used, so that nested expressions in an unused
+        // default value cannot fail
+        let argument = if missing == (kind != '+') {
+            expand_argument(argument, flags, filters, variables, false)?
+        } else {
+            String::new()
+        };
+
+        // An alternate value is empty by intention if the variable is not set,
+        // therefore the Fail and NoReplace flags do not apply
+        if kind == '+' {
+            return Ok(Expansion::Value(process_alternate_value(
+                op,
+                env_value.as_deref(),
+                &argument,
+                colored,
+            )));
+        }
+
+        if kind == '=' && missing {
+            variables.set(&lookup_name, &argument);
+        }
+
+        let result = match kind {
+            '?' => process_required_value(op, env_value, &argument, &var_name, colored)?,
+            _ => process_default_value(op, env_value, &argument, colored),
+        };
+
+        // Handle Fail, FailOnEmpty, FailOnUnset, NoReplace, NoReplaceUnset, and NoReplaceEmpty flags
+        return check_result(result, &var_name, original_variable, flags);
+    }
+
+    // Expand nested expressions in the operation data. Patterns and replacement strings match the
+    // expanded values literally.
+    let operation_data = match (operation, operation_data) {
+        (Some(':'), Some(data)) => Some(expand_argument(&data, flags, filters, variables, false)?),
+        (_, Some(data)) => Some(expand_argument(&data, flags, filters, variables, true)?),
+        (_, None) => None,
+    };
+
+    // Perform the specified operation, if any, on the value
+    let result = if let Some(op) = operation {
+        match op {
+            // Process '#' and '%' operations for pattern stripping
+            '#' | '%' => process_pattern_stripping(
+                op,
+                &value,
+                operation_data.as_deref(),
+                inner_expr,
+                colored,
+            ),
+            // Process '/' operation for pattern replacement
+            '/' => {
+                process_pattern_replacement(&value, operation_data.as_deref(), inner_expr, colored)
+            }
+            // Process ',', '^' and '~' operations for case conversion
+            ',' | '^' | '~' => {
+                process_case_conversion(op, &value, operation_data.as_deref(), inner_expr, colored)
+            }
+            // Process '@' operation for transformations like ${VAR@Q}
+            '@' => process_transformation(
+                env_value.as_deref(),
+                operation_data.as_deref(),
+                inner_expr,
+                colored,
+            ),
+            // Process ':' operation for substring extraction
+            ':' => {
+                process_substring_extraction(&value, operation_data.as_ref(), inner_expr, colored)
+            }
+            _ => return Err(format!("Invalid operation: {op}")),
+        }
+    } else {
+        Ok(value)
+    };
+
+    // Handle Fail, FailOnEmpty, FailOnUnset, NoReplace, NoReplaceUnset, and NoReplaceEmpty flags
+    return check_result(result?, &var_name, original_variable, flags);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::filters::Filter;
+    use colored::Colorize;
+    use std::env;
+
+    #[test]
+    fn test_process_inner_expression_invalid_operation() {
+        let flags = Flags::default();
+        let filters = Filters::default();
+        env::set_var("TEST_VAR", "Hello, world!");
+        let result =
+            process_inner_expression("TEST_VAR;", &flags, &filters, &mut Variables::default());
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err(), "Invalid character in expression: ;");
+    }
+
+    #[test]
+    fn test_process_inner_expression_basic_variable_replacement() {
+        let flags = Flags::default();
+        let filters = Filters::default();
+        env::set_var("TEST_VAR", "Hello, world!");
+        let result =
+            process_inner_expression("TEST_VAR", &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "Hello, world!");
+    }
+
+    #[test]
+    fn test_process_inner_expression_invalid_character_expression() {
+        env::set_var("TEST_VAR", "Hello, world!");
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let result =
+            process_inner_expression("TEST_VAR*", &flags, &filters, &mut Variables::default());
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err(), "Invalid character in expression: *");
+    }
+
+    #[test]
+    fn test_process_inner_expression_pattern_stripping_prefix() {
+        env::set_var("TEST_VAR", "Hello, world!");
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let result =
+            process_inner_expression("TEST_VAR#H", &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "ello, world!");
+    }
+
+    #[test]
+    fn test_process_inner_expression_pattern_stripping_suffix() {
+        env::set_var("TEST_VAR", "Hello, world!");
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let result =
+            process_inner_expression("TEST_VAR%d!", &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "Hello, worl");
+    }
+
+    #[test]
+    fn test_process_inner_expression_pattern_stripping_longest() {
+        env::set_var("IMAGE_VAR", "ghcr.io/containeroo/renvsubst:latest");
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let result =
+            process_inner_expression("IMAGE_VAR##*/", &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "renvsubst:latest");
+        let result =
+            process_inner_expression("IMAGE_VAR%%:*", &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "ghcr.io/containeroo/renvsubst");
+    }
+
+    #[test]
+    fn test_process_inner_expression_pattern_replacement() {
+        env::set_var("TEST_VAR", "Hello, world!");
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let result = process_inner_expression(
+            "TEST_VAR/world/moon",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "Hello, moon!");
+    }
+
+    #[test]
+    fn test_process_inner_expression_pattern_replacement_all() {
+        env::set_var("HOSTS_VAR", "Web-01.Example.COM");
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let result = process_inner_expression(
+            "HOSTS_VAR//[A-Z]/_&",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "_Web-01._Example._C_O_M");
+    }
+
+    #[test]
+    fn test_process_inner_expression_pattern_replacement_prefixed() {
+        env::set_var("TEST_VAR", "http://containeroo.ch!");
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let result = process_inner_expression(
+            "TEST_VAR/#http:\\/\\//https:\\/\\/",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "https://containeroo.ch!");
+    }
+
+    #[test]
+    fn test_process_inner_expression_pattern_replacement_suffixed() {
+        env::set_var("TEST_VAR", "Hello, http://containeroo.ch");
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let result = process_inner_expression(
+            "TEST_VAR/%.ch/.com",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "Hello, http://containeroo.com");
+    }
+
+    #[test]
+    fn test_render_replacement() {
+        assert_eq!(render_replacement("[&]", "match"), "[match]");
+        assert_eq!(render_replacement("\\&\\\\", "match"), "&\\");
+        assert_eq!(render_replacement("", "match"), "");
+    }
+
+    #[test]
+    fn test_process_pattern_replacement_trailing_backslash_error() {
+        let value = "Hello, world!";
+        let operation_data = Some("Hello\\/worl\\");
+        let inner_expr = "${VAR/Hello\\/worl\\}";
+        let colored = false;
+
+        let result = process_pattern_replacement(value, operation_data, inner_expr, colored);
+
+        assert_eq!(
+            result,
+            Err(String::from(
+                "\"${VAR/Hello\\/worl\\}\" - Trailing backslash"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_process_inner_expression_case_conversion_first_character_lowercase() {
+        env::set_var("TEST_VAR", "Hello, world!");
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let result =
+            process_inner_expression("TEST_VAR,", &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "hello, world!");
+    }
+
+    #[test]
+    fn test_process_inner_expression_case_conversion_first_character_uppercase() {
+        env::set_var("TEST_VAR", "Hello, world!");
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let result =
+            process_inner_expression("TEST_VAR^", &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "Hello, world!");
+    }
+
+    #[test]
+    fn test_process_inner_expression_case_conversion_all_lowercase() {
+        env::set_var("TEST_VAR", "Hello, world!");
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let result =
+            process_inner_expression("TEST_VAR,,", &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "hello, world!");
+    }
+
+    #[test]
+    fn test_process_inner_expression_case_conversion_all_uppercase() {
+        env::set_var("TEST_VAR", "Hello, world!");
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let result =
+            process_inner_expression("TEST_VAR^^", &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "HELLO, WORLD!");
+    }
+
+    #[test]
+    fn test_name_length() {
+        assert_eq!(name_length("VAR:-x", false), 3);
+        assert_eq!(name_length("db.host:-x", false), 2);
+        assert_eq!(name_length("db.host:-x", true), 7);
+        assert_eq!(name_length("server.max-size-", true), 15);
+        assert_eq!(name_length("server-port", true), 6);
+        assert_eq!(name_length("VAR-default", true), 3);
+        assert_eq!(name_length("a.-b", true), 1);
+        assert_eq!(name_length(".a", true), 0);
+        assert_eq!(name_length("ä", true), 0);
+    }
+
+    #[test]
+    fn test_process_inner_expression_relaxed_names() {
+        env::set_var("RELAXED_DB_HOST", "db.example.com");
+        env::remove_var("RELAXED_SERVER_PORT");
+        env::remove_var("RELAXED_UNSET");
+        env::set_var("RELAXED_DB_REF", "relaxed.db.host");
+        env::set_var("RELAXED_DB_PORT", "5432");
+        let mut flags = Flags::default();
+        let filters = Filters::default();
+
+        // Without the flag, dots are invalid
+        let result = process_inner_expression(
+            "relaxed.db.host",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(
+            result,
+            Err("Invalid character in expression: .".to_string())
+        );
+
+        flags
+            .set(Flag::RelaxedNames, "--relaxed-names", true)
+            .unwrap();
+        let mut variables = Variables::default();
+        let cases = [
+            ("relaxed.db.host", "db.example.com"),
+            ("Relaxed.DB.host", "db.example.com"),
+            ("#relaxed.db.host", "14"),
+            ("relaxed.db.host | upper", "DB.EXAMPLE.COM"),
+            ("relaxed.db.host#db.", "example.com"),
+            ("relaxed.server-port:=8080", "8080"),
+            ("RELAXED_SERVER_PORT", "8080"),
+            ("relaxed.server-port-", "8080"),
+            // A dash without a dot in the name is still an operator
+            ("RELAXED_UNSET-default", "default"),
+            ("relaxed_unset-default", "default"),
+            ("RELAXED_DB_HOST-default", "db.example.com"),
+            // Indirections accept relaxed names as well
+            ("!relaxed.db.ref", "db.example.com"),
+            ("!relaxed.db.ref#db.", "example.com"),
+        ];
+        for (expression, expected) in cases {
+            let result = process_inner_expression(expression, &flags, &filters, &mut variables);
+            assert_eq!(result, Ok(expected.to_string()), "{expression}");
+        }
+        let result = process_arithmetic(
+            "${relaxed.db.port} + relaxed_db_port",
+            &flags,
+            &filters,
+            &mut variables,
+        );
+        assert_eq!(result, Ok("10864".to_string()));
+
+        // Errors and filters use the original spelling
+        flags
+            .set(Flag::FailOnUnset, "--fail-on-unset", true)
+            .unwrap();
+        let result = process_inner_expression("relaxed.unset", &flags, &filters, &mut variables);
+        assert_eq!(
+            result,
+            Err("environment variable 'relaxed.unset' is not set".to_string())
+        );
+
+        let mut filters = Filters::default();
+        filters
+            .add(Filter::Prefix, "--prefix", Some("relaxed."), &mut [].iter())
+            .unwrap();
+        let result = process_inner_expression("RELAXED_DB_HOST", &flags, &filters, &mut variables);
+        assert_eq!(result, Ok("${RELAXED_DB_HOST}".to_string()));
+        let result = process_inner_expression("relaxed.db.host", &flags, &filters, &mut variables);
+        assert_eq!(result, Ok("db.example.com".to_string()));
+    }
+
+    #[test]
+    fn test_process_inner_expression_transformation() {
+        env::set_var("TRANSFORM_VAR", "it's élan\\t$HOME");
+        env::set_var("TRANSFORM_EMPTY", "");
+        env::remove_var("TRANSFORM_UNSET");
+        let flags = Flags::default();
+        let filters = Filters::default();
+
+        let cases = [
+            ("TRANSFORM_VAR@Q", "'it'\\''s élan\\t$HOME'"),
+            ("TRANSFORM_VAR@U", "IT'S ÉLAN\\T$HOME"),
+            ("TRANSFORM_VAR@L", "it's élan\\t$home"),
+            ("TRANSFORM_VAR@u", "It's élan\\t$HOME"),
+            ("TRANSFORM_VAR@E", "it's élan\t$HOME"),
+            ("TRANSFORM_EMPTY@Q", "''"),
+            ("TRANSFORM_UNSET@Q", ""),
+        ];
+        for (expression, expected) in cases {
+            let result =
+                process_inner_expression(expression, &flags, &filters, &mut Variables::default());
+            assert_eq!(result, Ok(expected.to_string()), "{expression}");
+        }
+
+        let result = process_inner_expression(
+            "TRANSFORM_VAR@X",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(
+            result,
+            Err("\"TRANSFORM_VAR@X\" - Invalid transformation: 'X'".to_string())
+        );
+
+        // Unset variables are handled by the flags
+        let mut flags = Flags::default();
+        flags
+            .set(Flag::NoReplaceUnset, "--no-replace-unset", true)
+            .unwrap();
+        let result = process_inner_expression(
+            "TRANSFORM_UNSET@Q",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result, Ok("${TRANSFORM_UNSET}".to_string()));
+    }
+
+    #[test]
+    fn test_process_inner_expression_case_conversion_pattern_and_toggle() {
+        env::set_var("CASE_TOGGLE_VAR", "émile Zola");
+        env::set_var("CASE_PATTERN", "aeiou");
+        let flags = Flags::default();
+        let filters = Filters::default();
+
+        let cases = [
+            ("CASE_TOGGLE_VAR^", "Émile Zola"),
+            ("CASE_TOGGLE_VAR~", "Émile Zola"),
+            ("CASE_TOGGLE_VAR~~", "ÉMILE zOLA"),
+            ("CASE_TOGGLE_VAR^^[aeiou]", "émIlE ZOlA"),
+            ("CASE_TOGGLE_VAR^^$CASE_PATTERN", "émile Zola"),
+            ("CASE_TOGGLE_VAR^^[$CASE_PATTERN]", "émIlE ZOlA"),
+        ];
+        for (expression, expected) in cases {
+            let result =
+                process_inner_expression(expression, &flags, &filters, &mut Variables::default());
+            assert_eq!(result, Ok(expected.to_string()), "{expression}");
+        }
+    }
+
+    #[test]
+    fn test_process_inner_expression_default_value() {
+        let flags = Flags::default();
+        let filters = Filters::default();
+
+        let result = process_inner_expression(
+            "UNSET_VAR:-default",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "default");
+    }
+
+    #[test]
+    fn test_process_inner_expression_default_value_empty_string() {
+        let flags = Flags::default();
+        let filters = Filters::default();
+
+        let result =
+            process_inner_expression("UNSET_VAR:-", &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "");
+    }
+
+    #[test]
+    fn test_process_inner_expression_default_value_set_but_empty() {
+        let flags = Flags::default();
+        let filters = Filters::default();
+        env::set_var("EMPTY_DEFAULT_VAR", "");
+
+        // ${VAR:-default} applies the default if the variable is empty
+        let result = process_inner_expression(
+            "EMPTY_DEFAULT_VAR:-default",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "default");
+
+        // ${VAR-default} keeps the empty value of a set variable
+        let result = process_inner_expression(
+            "EMPTY_DEFAULT_VAR-default",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "");
+    }
+
+    #[test]
+    fn test_process_inner_expression_default_value_unset() {
+        let flags = Flags::default();
+        let filters = Filters::default();
+
+        let result = process_inner_expression(
+            "UNSET_VAR-default",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "default");
+
+        env::set_var("SET_DEFAULT_VAR", "value");
+        let result = process_inner_expression(
+            "SET_DEFAULT_VAR-default",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "value");
+    }
+
+    #[test]
+    fn test_process_inner_expression_assign_default_value() {
+        let flags = Flags::default();
+        let filters = Filters::default();
+        let mut variables = Variables::default();
+        env::set_var("EMPTY_ASSIGN_VAR", "");
+
+        // ${VAR:=default} assigns the default if the variable is unset
+        let result = process_inner_expression(
+            "UNSET_ASSIGN_VAR:=default",
+            &flags,
+            &filters,
+            &mut variables,
+        );
+        assert_eq!(result.unwrap(), "default");
+        let result = process_inner_expression("UNSET_ASSIGN_VAR", &flags, &filters, &mut variables);
+        assert_eq!(result.unwrap(), "default");
+
+        // An assigned variable is not overwritten
+        let result =
+            process_inner_expression("UNSET_ASSIGN_VAR:=other", &flags, &filters, &mut variables);
+        assert_eq!(result.unwrap(), "default");
+
+        // ${VAR=default} keeps the empty value of a set variable
+        let result =
+            process_inner_expression("EMPTY_ASSIGN_VAR=default", &flags, &filters, &mut variables);
+        assert_eq!(result.unwrap(), "");
+
+        // ${VAR:=default} assigns the default if the variable is empty
+        let result = process_inner_expression(
+            "EMPTY_ASSIGN_VAR:=default",
+            &flags,
+            &filters,
+            &mut variables,
+        );
+        assert_eq!(result.unwrap(), "default");
+        assert_eq!(
+            variables.get("EMPTY_ASSIGN_VAR"),
+            Some("default".to_string())
+        );
+    }
+
+    #[test]
+    fn test_process_inner_expression_assign_default_value_filtered() {
+        let flags = Flags::default();
+        let mut filters = Filters::default();
+        let mut variables = Variables::default();
+        filters
+            .add(Filter::Prefix, "--prefix", Some("PREFIX"), &mut [].iter())
+            .expect("Failed to set prefix filter");
+
+        let result = process_inner_expression(
+            "FILTERED_ASSIGN_VAR:=default",
+            &flags,
+            &filters,
+            &mut variables,
+        );
+        assert_eq!(result.unwrap(), "${FILTERED_ASSIGN_VAR}");
+        assert_eq!(variables.get("FILTERED_ASSIGN_VAR"), None);
+    }
+
+    #[test]
+    fn test_process_inner_expression_required_value() {
+        let flags = Flags::default();
+        let filters = Filters::default();
+        env::set_var("REQUIRED_VAR", "value");
+        env::set_var("EMPTY_REQUIRED_VAR", "");
+
+        let result = process_inner_expression(
+            "REQUIRED_VAR:?must be set",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "value");
+
+        let result = process_inner_expression(
+            "UNSET_REQUIRED_VAR:?must be set",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            "environment variable 'UNSET_REQUIRED_VAR': must be set"
+        );
+
+        // ${VAR?message} accepts an empty value
+        let result = process_inner_expression(
+            "EMPTY_REQUIRED_VAR?must be set",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "");
+
+        let result = process_inner_expression(
+            "EMPTY_REQUIRED_VAR:?",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            "environment variable 'EMPTY_REQUIRED_VAR' is empty"
+        );
+    }
+
+    #[test]
+    fn test_process_inner_expression_alternate_value() {
+        let mut flags = Flags::default();
+        let filters = Filters::default();
+        env::set_var("TLS_ENABLED", "true");
+        env::set_var("EMPTY_TLS_ENABLED", "");
+
+        let result = process_inner_expression(
+            "TLS_ENABLED:+--tls-cert=/path",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "--tls-cert=/path");
+
+        let result = process_inner_expression(
+            "EMPTY_TLS_ENABLED:+--tls-cert=/path",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "");
+
+        let result = process_inner_expression(
+            "EMPTY_TLS_ENABLED+--tls-cert=/path",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "--tls-cert=/path");
+
+        // An unset variable results in an empty string, even with --fail
+        flags
+            .set(Flag::Fail, "--fail", true)
+            .expect("Failed to set Fail flag");
+        let result = process_inner_expression(
+            "UNSET_TLS_ENABLED:+--tls-cert=/path",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "");
+    }
+
+    #[test]
+    fn test_process_inner_expression_nested_expansion() {
+        let mut flags = Flags::default();
+        let filters = Filters::default();
+        env::set_var("NESTED_GLOBAL_URL", "https://global.example.com");
+        env::set_var("NESTED_IMAGE", "registry.example.com/team/app:1.0");
+        env::set_var("NESTED_REGISTRY", "registry.example.com/");
+        env::set_var("NESTED_PATTERN", "*");
+        env::set_var("NESTED_SEPARATOR", "/");
+
+        // Cascading default values
+        let result = process_inner_expression(
+            "NESTED_SERVICE_URL:-${NESTED_GLOBAL_URL:-http://localhost}",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "https://global.example.com");
+
+        let result = process_inner_expression(
+            "NESTED_SERVICE_URL:-${NESTED_UNSET_URL:-http://localhost:$NESTED_UNSET_PORT}",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "http://localhost:");
+
+        // Variables in patterns and replacements are matched literally
+        let result = process_inner_expression(
+            "NESTED_IMAGE#${NESTED_REGISTRY}",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "team/app:1.0");
+
+        let result = process_inner_expression(
+            "NESTED_IMAGE#${NESTED_PATTERN}",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "registry.example.com/team/app:1.0");
+
+        let result = process_inner_expression(
+            "NESTED_IMAGE//${NESTED_SEPARATOR}/$NESTED_PATTERN",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "registry.example.com*team*app:1.0");
+
+        // An unused default value is not expanded, so the Fail flag does not apply to it
+        flags
+            .set(Flag::FailOnUnset, "--fail-on-unset", true)
+            .expect("Failed to set FailOnUnset flag");
+        let result = process_inner_expression(
+            "NESTED_GLOBAL_URL:-${NESTED_UNSET_URL}",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "https://global.example.com");
+
+        let result = process_inner_expression(
+            "NESTED_SERVICE_URL:-${NESTED_UNSET_URL}",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(
+            result,
+            Err("environment variable 'NESTED_UNSET_URL' is not set".to_string())
+        );
+    }
+
+    #[test]
+    fn test_process_inner_expression_indirection() {
+        let mut filters = Filters::default();
+        let flags = Flags::default();
+        env::set_var("INDIRECT_STAGE", "INDIRECT_DB_URL_PROD");
+        env::set_var("INDIRECT_DB_URL_PROD", "postgres://prod");
+        env::set_var("INDIRECT_INVALID", "not a name");
+
+        let result = process_inner_expression(
+            "!INDIRECT_STAGE",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "postgres://prod");
+
+        // The operation applies to the referenced variable
+        let result = process_inner_expression(
+            "!INDIRECT_STAGE#postgres://",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "prod");
+
+        let mut variables = Variables::default();
+        variables.set("INDIRECT_ASSIGNED", "INDIRECT_DB_URL_DEV");
+        let result = process_inner_expression(
+            "!INDIRECT_ASSIGNED:-postgres://localhost",
+            &flags,
+            &filters,
+            &mut variables,
+        );
+        assert_eq!(result.unwrap(), "postgres://localhost");
+
+        let result = process_inner_expression(
+            "!INDIRECT_UNSET",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(
+            result,
+            Err("Invalid indirect expansion: variable 'INDIRECT_UNSET' is not set".to_string())
+        );
 
-    let original_variable = format!("${{{var_name}}}");
+        let result = process_inner_expression(
+            "!INDIRECT_INVALID",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(
+            result,
+            Err("Invalid indirect expansion: 'not a name' of variable 'INDIRECT_INVALID' is not a valid variable name".to_string())
+        );
 
-    // Check if the variable name matches any filters
-    if filters.matches(&var_name) == Some(false) {
-        return Ok(colorize_text(colored, original_variable, Color::Magenta));
+        let result = process_inner_expression("!-", &flags, &filters, &mut Variables::default());
+        assert_eq!(result, Err("Invalid indirect expansion: ${!-}".to_string()));
+
+        // Filters apply to the name of the reference
+        filters
+            .add(Filter::Prefix, "--prefix", Some("OTHER_"), &mut [].iter())
+            .expect("Failed to add prefix filter");
+        let result = process_inner_expression(
+            "!INDIRECT_STAGE",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "${!INDIRECT_STAGE}");
     }
 
-    // Handle Fail, FailOnEmpty, FailOnUnset, NoReplace, NoReplaceUnset, and NoReplaceEmpty flags
-    let result = handle_flags_on_result(result?, &var_name, &original_variable, flags)?;
+    #[test]
+    fn test_process_inner_expression_name_listing() {
+        let mut filters = Filters::default();
+        let flags = Flags::default();
+        env::set_var("LISTING_DB_HOST", "localhost");
+        env::set_var("LISTING_DB_PORT", "5432");
+        env::set_var("LISTING_CACHE_HOST", "localhost");
+        let mut variables = Variables::default();
+        variables.set("LISTING_DB_NAME", "app");
 
-    return Ok(result);
-}
+        let result = process_inner_expression("!LISTING_DB_*", &flags, &filters, &mut variables);
+        assert_eq!(
+            result.unwrap(),
+            "LISTING_DB_HOST LISTING_DB_NAME LISTING_DB_PORT"
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let result = process_inner_expression("!LISTING_DB_@", &flags, &filters, &mut variables);
+        assert_eq!(
+            result.unwrap(),
+            "LISTING_DB_HOST LISTING_DB_NAME LISTING_DB_PORT"
+        );
 
-    use crate::filters::Filter;
-    use colored::Colorize;
+        let result = process_inner_expression("!LISTING_NONE*", &flags, &filters, &mut variables);
+        assert_eq!(result.unwrap(), "");
+
+        // Names excluded by the filters are not listed
+        filters
+            .add(Filter::Suffix, "--suffix", Some("_HOST"), &mut [].iter())
+            .expect("Failed to add suffix filter");
+        let result = process_inner_expression("!LISTING_*", &flags, &filters, &mut variables);
+        assert_eq!(result.unwrap(), "LISTING_CACHE_HOST LISTING_DB_HOST");
+    }
 
     #[test]
-    fn test_process_inner_expression_invalid_operation() {
+    fn test_process_inner_expression_pipeline_without_spaces() {
         let flags = Flags::default();
         let filters = Filters::default();
-        env::set_var("TEST_VAR", "Hello, world!");
-        let result = process_inner_expression("TEST_VAR=", &flags, &filters);
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Invalid operation: =");
+        let mut variables = Variables::default();
+
+        for (expression, function) in [
+            ("A|upper", "upper"),
+            ("A |upper", "upper"),
+            ("A| default:x", "default"),
+        ] {
+            assert_eq!(
+                process_inner_expression(expression, &flags, &filters, &mut variables),
+                Err(format!(
+                    "\"{expression}\" - Pipeline separators need spaces around them, e.g. 'A | {function}'"
+                ))
+            );
+        }
+        assert_eq!(
+            process_inner_expression("A|uppercase", &flags, &filters, &mut variables),
+            Err("Invalid character in expression: |".to_string())
+        );
+        assert_eq!(
+            process_inner_expression("A;|upper", &flags, &filters, &mut variables),
+            Err("Invalid character in expression: ;".to_string())
+        );
     }
 
     #[test]
-    fn test_process_inner_expression_basic_variable_replacement() {
-        let flags = Flags::default();
-        let filters = Filters::default();
-        env::set_var("TEST_VAR", "Hello, world!");
-        let result = process_inner_expression("TEST_VAR", &flags, &filters);
-        assert_eq!(result.unwrap(), "Hello, world!");
+    fn test_split_pipeline() {
+        assert_eq!(
+            split_pipeline("HOST | lower | default:x|y"),
+            Some(vec![
+                "HOST".to_string(),
+                "lower".to_string(),
+                "default:x|y".to_string()
+            ])
+        );
+        assert_eq!(
+            split_pipeline("HOST:-${A | lower}\\|b | upper"),
+            Some(vec![
+                "HOST:-${A | lower}|b".to_string(),
+                "upper".to_string()
+            ])
+        );
+        assert_eq!(split_pipeline("HOST"), None);
+        assert_eq!(split_pipeline("SEPARATOR:-|"), None);
+        assert_eq!(split_pipeline("SEPARATOR:- | -"), None);
+        assert_eq!(split_pipeline("FORMATS:-json|yaml"), None);
+        assert_eq!(split_pipeline("MODE:-read | write"), None);
+        assert_eq!(split_pipeline("HOST |lower"), None);
+        assert_eq!(
+            split_pipeline("HOST | lowercase"),
+            Some(vec!["HOST".to_string(), "lowercase".to_string()])
+        );
     }
 
     #[test]
-    fn test_process_inner_expression_invalid_character_expression() {
-        env::set_var("TEST_VAR", "Hello, world!");
+    fn test_process_inner_expression_pipe_in_default_value() {
         let flags = Flags::default();
         let filters = Filters::default();
-        let result = process_inner_expression("TEST_VAR@", &flags, &filters);
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Invalid character in expression: @");
+
+        for (expression, expected) in [
+            ("UNSET_PIPE_VAR:-a|b", "a|b"),
+            ("UNSET_PIPE_VAR:-json|yaml", "json|yaml"),
+            ("UNSET_PIPE_VAR:-read|write", "read|write"),
+            ("UNSET_PIPE_VAR:-read | write", "read | write"),
+            ("UNSET_PIPE_VAR:-json | upper", "JSON"),
+        ] {
+            let result =
+                process_inner_expression(expression, &flags, &filters, &mut Variables::default());
+            assert_eq!(result, Ok(expected.to_string()), "{expression}");
+        }
     }
 
     #[test]
-    fn test_process_inner_expression_pattern_stripping_prefix() {
-        env::set_var("TEST_VAR", "Hello, world!");
-        let flags = Flags::default();
-        let filters = Filters::default();
-        let result = process_inner_expression("TEST_VAR#H", &flags, &filters);
-        assert_eq!(result.unwrap(), "ello, world!");
+    fn test_process_inner_expression_pipeline() {
+        let mut flags = Flags::default();
+        let mut filters = Filters::default();
+        env::set_var("PIPELINE_HOST", "  Example.COM ");
+
+        let result = process_inner_expression(
+            "PIPELINE_HOST | lower | trim",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "example.com");
+
+        // The first segment can use operators and functions can have arguments
+        let result = process_inner_expression(
+            "PIPELINE_UNSET:- | upper",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "");
+        let result = process_inner_expression(
+            "PIPELINE_UNSET | default:${PIPELINE_HOST} | trim | capitalize",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "Example.COM");
+
+        let result = process_inner_expression(
+            "PIPELINE_HOST | lowercase",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(
+            result,
+            Err("\"PIPELINE_HOST | lowercase\" - Unknown function: 'lowercase'".to_string())
+        );
+
+        // The NoReplace flags keep the variable as is
+        flags
+            .set(Flag::NoReplaceUnset, "--no-replace-unset", true)
+            .expect("Failed to set NoReplaceUnset flag");
+        let result = process_inner_expression(
+            "PIPELINE_UNSET | upper",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "${PIPELINE_UNSET}");
+
+        filters
+            .add(Filter::Prefix, "--prefix", Some("OTHER_"), &mut [].iter())
+            .expect("Failed to add prefix filter");
+        let result = process_inner_expression(
+            "PIPELINE_HOST | upper",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "${PIPELINE_HOST}");
     }
 
     #[test]
-    fn test_process_inner_expression_pattern_stripping_suffix() {
-        env::set_var("TEST_VAR", "Hello, world!");
+    fn test_process_inner_expression_pipeline_encoding() {
         let flags = Flags::default();
         let filters = Filters::default();
-        let result = process_inner_expression("TEST_VAR%d!", &flags, &filters);
-        assert_eq!(result.unwrap(), "Hello, worl");
+        env::set_var("ENCODING_PASSWORD", "p@ss:word");
+        env::set_var("ENCODING_INVALID", "not base64!");
+
+        let result = process_inner_expression(
+            "ENCODING_PASSWORD | base64",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "cEBzczp3b3Jk");
+
+        let result = process_inner_expression(
+            "ENCODING_PASSWORD | urlencode",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "p%40ss%3Aword");
+
+        // Decoding errors contain the variable name
+        let result = process_inner_expression(
+            "ENCODING_INVALID | base64_decode",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(
+            result,
+            Err(
+                "\"ENCODING_INVALID | base64_decode\" - Invalid base64: invalid character '!'"
+                    .to_string()
+            )
+        );
     }
 
     #[test]
-    fn test_process_inner_expression_pattern_replacement() {
-        env::set_var("TEST_VAR", "Hello, world!");
+    fn test_process_arithmetic() {
         let flags = Flags::default();
-        let filters = Filters::default();
-        let result = process_inner_expression("TEST_VAR/world/moon", &flags, &filters);
-        assert_eq!(result.unwrap(), "Hello, moon!");
+        let mut filters = Filters::default();
+        env::set_var("ARITHMETIC_WORKERS", "4");
+        env::set_var("ARITHMETIC_PORT", " 8080\n");
+        env::set_var("ARITHMETIC_NAME", "eight");
+
+        let result = process_arithmetic(
+            "ARITHMETIC_WORKERS * 2 + 1",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "9");
+
+        let result = process_arithmetic(
+            "$ARITHMETIC_PORT + ${ARITHMETIC_OFFSET:-10}",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "8090");
+
+        let result = process_arithmetic(
+            "ARITHMETIC_UNSET + 1",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(
+            result,
+            Err(
+                "\"$((ARITHMETIC_UNSET + 1))\" - Variable 'ARITHMETIC_UNSET' is not set"
+                    .to_string()
+            )
+        );
+
+        let result = process_arithmetic(
+            "ARITHMETIC_NAME + 1",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(
+            result,
+            Err(
+                "\"$((ARITHMETIC_NAME + 1))\" - Variable 'ARITHMETIC_NAME' is not an integer: 'eight'"
+                    .to_string()
+            )
+        );
+
+        let result = process_arithmetic(
+            "ARITHMETIC_WORKERS / 0",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(
+            result,
+            Err("\"$((ARITHMETIC_WORKERS / 0))\" - Division by zero".to_string())
+        );
+
+        filters
+            .add(Filter::Prefix, "--prefix", Some("OTHER_"), &mut [].iter())
+            .expect("Failed to add prefix filter");
+        let result = process_arithmetic(
+            "ARITHMETIC_WORKERS + 1",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "$((ARITHMETIC_WORKERS + 1))");
     }
 
     #[test]
-    fn test_process_inner_expression_pattern_replacement_prefixed() {
-        env::set_var("TEST_VAR", "http://containeroo.ch!");
-        let flags = Flags::default();
+    fn test_arithmetic_references() {
+        assert_eq!(
+            arithmetic_references("i + $j * ${k} - ${#l} + ${m:-n + 1} + 0x1f", false),
+            vec![
+                ("i", false),
+                ("j", false),
+                ("k", false),
+                ("l", false),
+                ("m", true)
+            ]
+        );
+        assert_eq!(
+            arithmetic_references("${db.port:+${OFFSET}} + (1)", true),
+            vec![("db.port", true)]
+        );
+        assert_eq!(arithmetic_references("", false), vec![]);
+    }
+
+    #[test]
+    fn test_process_arithmetic_kept_as_is() {
+        env::set_var("ARITHMETIC_KEPT_APP_PORT", "8080");
+        env::set_var("ARITHMETIC_KEPT_EMPTY", "");
+
+        // Variables excluded by the filters keep the expression as is
+        let mut filters = Filters::default();
+        filters
+            .add(
+                Filter::Prefix,
+                "--prefix",
+                Some("ARITHMETIC_KEPT_APP_"),
+                &mut [].iter(),
+            )
+            .expect("Failed to add prefix filter");
+        for expression in [
+            "i + 1",
+            "$i + 1",
+            "${i} + 1",
+            "${i:-1} + ARITHMETIC_KEPT_APP_PORT",
+        ] {
+            let result = process_arithmetic(
+                expression,
+                &Flags::default(),
+                &filters,
+                &mut Variables::default(),
+            );
+            assert_eq!(result, Ok(format!("$(({expression}))")), "{expression}");
+        }
+        let result = process_arithmetic(
+            "ARITHMETIC_KEPT_APP_PORT + 1",
+            &Flags::default(),
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "8081");
+
+        // Unset and empty variables keep the expression as is with the NoReplace flags, unless a
+        // value operator applies
         let filters = Filters::default();
-        let result =
-            process_inner_expression("TEST_VAR/#http:\\/\\//https:\\/\\/", &flags, &filters);
-        assert_eq!(result.unwrap(), "https://containeroo.ch!");
+        for (flag, name, expressions, default) in [
+            (
+                Flag::NoReplaceUnset,
+                "--no-replace-unset",
+                ["ARITHMETIC_KEPT_UNSET + 1", "$ARITHMETIC_KEPT_UNSET + 1"],
+                "${ARITHMETIC_KEPT_UNSET:-1} + 1",
+            ),
+            (
+                Flag::NoReplaceEmpty,
+                "--no-replace-empty",
+                ["ARITHMETIC_KEPT_EMPTY + 1", "${ARITHMETIC_KEPT_EMPTY} + 1"],
+                "${ARITHMETIC_KEPT_EMPTY:-1} + 1",
+            ),
+            (
+                Flag::NoReplace,
+                "--no-replace",
+                ["ARITHMETIC_KEPT_UNSET + 1", "$ARITHMETIC_KEPT_EMPTY + 1"],
+                "${ARITHMETIC_KEPT_UNSET:-1} + 1",
+            ),
+        ] {
+            let mut flags = Flags::default();
+            flags.set(flag, name, true).unwrap();
+            for expression in expressions {
+                let result =
+                    process_arithmetic(expression, &flags, &filters, &mut Variables::default());
+                assert_eq!(
+                    result,
+                    Ok(format!("$(({expression}))")),
+                    "{name} {expression}"
+                );
+            }
+            let result = process_arithmetic(default, &flags, &filters, &mut Variables::default());
+            assert_eq!(result.unwrap(), "2", "{name} {default}");
+        }
     }
 
     #[test]
-    fn test_process_inner_expression_pattern_replacement_suffixed() {
-        env::set_var("TEST_VAR", "Hello, http://containeroo.ch");
+    fn test_process_inner_expression_length() {
         let flags = Flags::default();
         let filters = Filters::default();
-        let result = process_inner_expression("TEST_VAR/%.ch/.com", &flags, &filters);
-        assert_eq!(result.unwrap(), "Hello, http://containeroo.com");
+        env::set_var("LENGTH_VAR", "Grüezi");
+
+        let result =
+            process_inner_expression("#LENGTH_VAR", &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "6");
+
+        let result = process_inner_expression(
+            "#UNSET_LENGTH_VAR",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "0");
     }
 
     #[test]
-    fn test_process_pattern_replacement_trailing_backslash_error() {
-        let value = "Hello, world!";
-        let operation_data = Some("Hello\\/worl\\");
-        let inner_expr = "${VAR/Hello\\/worl\\}";
-        let colored = false;
+    fn test_process_length_flags_and_filters() {
+        let mut flags = Flags::default();
+        flags
+            .set(Flag::FailOnUnset, "--fail-on-unset", true)
+            .expect("Failed to set FailOnUnset flag");
+        let result = process_length(
+            "UNSET_LENGTH_VAR",
+            &flags,
+            &Filters::default(),
+            &Variables::default(),
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            "environment variable 'UNSET_LENGTH_VAR' is not set"
+        );
 
-        let result = process_pattern_replacement(value, operation_data, inner_expr, colored);
+        let mut flags = Flags::default();
+        flags
+            .set(Flag::NoReplaceUnset, "--no-replace-unset", true)
+            .expect("Failed to set NoReplaceUnset flag");
+        let result = process_length(
+            "UNSET_LENGTH_VAR",
+            &flags,
+            &Filters::default(),
+            &Variables::default(),
+        );
+        assert_eq!(
+            result.unwrap(),
+            Expansion::Kept("${#UNSET_LENGTH_VAR}".to_string(), Color::Red)
+        );
 
+        let mut filters = Filters::default();
+        filters
+            .add(Filter::Prefix, "--prefix", Some("PREFIX"), &mut [].iter())
+            .expect("Failed to set prefix filter");
+        let result = process_length(
+            "UNSET_LENGTH_VAR",
+            &Flags::default(),
+            &filters,
+            &Variables::default(),
+        );
         assert_eq!(
-            result,
-            Err(String::from(
-                "\"${VAR/Hello\\/worl\\}\" - Trailing backslash"
-            ))
+            result.unwrap(),
+            Expansion::Kept("${#UNSET_LENGTH_VAR}".to_string(), Color::Magenta)
         );
     }
 
     #[test]
-    fn test_process_inner_expression_case_conversion_first_character_lowercase() {
-        env::set_var("TEST_VAR", "Hello, world!");
-        let flags = Flags::default();
+    fn test_expand_inner_expression_kept() {
+        env::set_var("EXPANSION_LITERAL", "${EXPANSION_LITERAL}");
+        let mut flags = Flags::default();
+        flags
+            .set(Flag::NoReplaceUnset, "--no-replace-unset", true)
+            .expect("Failed to set NoReplaceUnset flag");
         let filters = Filters::default();
-        let result = process_inner_expression("TEST_VAR,", &flags, &filters);
-        assert_eq!(result.unwrap(), "hello, world!");
+
+        // A value that looks like the expression is still a value
+        for expression in ["EXPANSION_LITERAL", "EXPANSION_LITERAL | trim"] {
+            assert_eq!(
+                expand_inner_expression(expression, &flags, &filters, &mut Variables::default()),
+                Ok(Expansion::Value("${EXPANSION_LITERAL}".to_string()))
+            );
+        }
+        for (expression, original) in [
+            ("EXPANSION_UNSET", "${EXPANSION_UNSET}"),
+            ("EXPANSION_UNSET:2", "${EXPANSION_UNSET}"),
+            ("#EXPANSION_UNSET", "${#EXPANSION_UNSET}"),
+            ("EXPANSION_UNSET | upper", "${EXPANSION_UNSET}"),
+        ] {
+            assert_eq!(
+                expand_inner_expression(expression, &flags, &filters, &mut Variables::default()),
+                Ok(Expansion::Kept(original.to_string(), Color::Red)),
+                "{expression}"
+            );
+        }
     }
 
     #[test]
-    fn test_process_inner_expression_case_conversion_first_character_uppercase() {
-        env::set_var("TEST_VAR", "Hello, world!");
-        let flags = Flags::default();
-        let filters = Filters::default();
-        let result = process_inner_expression("TEST_VAR^", &flags, &filters);
-        assert_eq!(result.unwrap(), "Hello, world!");
+    fn test_is_valid_name() {
+        assert!(is_valid_name("VAR"));
+        assert!(is_valid_name("_var_1"));
+        assert!(!is_valid_name("1VAR"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("VAR-1"));
     }
 
     #[test]
-    fn test_process_inner_expression_case_conversion_all_lowercase() {
-        env::set_var("TEST_VAR", "Hello, world!");
-        let flags = Flags::default();
-        let filters = Filters::default();
-        let result = process_inner_expression("TEST_VAR,,", &flags, &filters);
-        assert_eq!(result.unwrap(), "hello, world!");
+    fn test_process_alternate_value() {
+        assert_eq!(process_alternate_value('+', None, "alt", false), "");
+        assert_eq!(process_alternate_value(':', Some(""), "alt", false), "");
+        assert_eq!(process_alternate_value('+', Some(""), "alt", false), "alt");
+        assert_eq!(
+            process_alternate_value(':', Some("value"), "alt", true),
+            "alt".blue().to_string()
+        );
     }
 
     #[test]
-    fn test_process_inner_expression_case_conversion_all_uppercase() {
-        env::set_var("TEST_VAR", "Hello, world!");
-        let flags = Flags::default();
-        let filters = Filters::default();
-        let result = process_inner_expression("TEST_VAR^^", &flags, &filters);
-        assert_eq!(result.unwrap(), "HELLO, WORLD!");
+    fn test_process_required_value() {
+        let result = process_required_value('?', None, "", "VAR", false);
+        assert_eq!(result.unwrap_err(), "environment variable 'VAR' is not set");
+
+        let result = process_required_value('?', None, "missing", "VAR", false);
+        assert_eq!(result.unwrap_err(), "environment variable 'VAR': missing");
+
+        let result = process_required_value(':', Some(String::new()), "missing", "VAR", false);
+        assert_eq!(result.unwrap_err(), "environment variable 'VAR': missing");
+
+        let result = process_required_value(':', Some("value".to_string()), "", "VAR", true);
+        assert_eq!(result.unwrap(), "value".green().to_string());
     }
 
     #[test]
-    fn test_process_inner_expression_default_value() {
-        let flags = Flags::default();
-        let filters = Filters::default();
-
-        let result = process_inner_expression("UNSET_VAR:-default", &flags, &filters);
-        assert_eq!(result.unwrap(), "default");
+    fn test_process_default_value() {
+        // Test unset variable
+        let result = process_default_value('-', None, "default", true);
+        assert_eq!(result, "default".yellow().to_string());
+
+        // Test empty variable
+        let result = process_default_value(':', Some(String::new()), "default", false);
+        assert_eq!(result, "default");
+        let result = process_default_value('-', Some(String::new()), "default", false);
+        assert_eq!(result, "");
+
+        // Test set variable
+        let result = process_default_value(':', Some("value".to_string()), "default", true);
+        assert_eq!(result, "value".green().to_string());
     }
 
     #[test]
-    fn test_process_inner_expression_default_value_empty_string() {
+    fn test_process_inner_expression_substring_extraction() {
         let flags = Flags::default();
         let filters = Filters::default();
 
-        let result = process_inner_expression("UNSET_VAR:-", &flags, &filters);
-        assert_eq!(result.unwrap(), "");
+        let result =
+            process_inner_expression("TEST_VAR:7:5", &flags, &filters, &mut Variables::default());
+        assert_eq!(result.unwrap(), "world");
     }
 
     #[test]
-    fn test_process_inner_expression_substring_extraction() {
+    fn test_process_inner_expression_substring_extraction_negative_offset() {
+        env::set_var("SUBSTRING_VAR", "renvsubst");
         let flags = Flags::default();
         let filters = Filters::default();
 
-        let result = process_inner_expression("TEST_VAR:7:5", &flags, &filters);
-        assert_eq!(result.unwrap(), "world");
+        let result = process_inner_expression(
+            "SUBSTRING_VAR: -5",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "subst");
+
+        let result = process_inner_expression(
+            "SUBSTRING_VAR:1:-5",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "env");
+
+        // Test ${VAR:-5} is still a default value
+        let result = process_inner_expression(
+            "SUBSTRING_VAR:-5",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
+        assert_eq!(result.unwrap(), "renvsubst");
     }
 
     #[test]
@@ -596,7 +2618,8 @@ mod tests {
         flags
             .set(Flag::Fail, "--fail", true)
             .expect("Failed to set Fail flag");
-        let result = process_inner_expression("UNSET_VAR", &flags, &filters);
+        let result =
+            process_inner_expression("UNSET_VAR", &flags, &filters, &mut Variables::default());
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err(),
@@ -612,7 +2635,12 @@ mod tests {
         flags
             .set(Flag::NoReplace, "--no-replace", true)
             .expect("Failed to set NoReplace flag");
-        let result = process_inner_expression("NOT_FOUND_VARIALBE", &flags, &filters);
+        let result = process_inner_expression(
+            "NOT_FOUND_VARIALBE",
+            &flags,
+            &filters,
+            &mut Variables::default(),
+        );
         assert_eq!(result.unwrap(), "${NOT_FOUND_VARIALBE}");
     }
 
@@ -624,7 +2652,8 @@ mod tests {
 
         let filter_result = filters.add(Filter::Prefix, "--prefix", Some("PREFIX"), &mut [].iter());
         assert!(filter_result.is_ok());
-        let result = process_inner_expression("TEST_VAR", &flags, &filters);
+        let result =
+            process_inner_expression("TEST_VAR", &flags, &filters, &mut Variables::default());
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "${TEST_VAR}");
     }
@@ -700,6 +2729,34 @@ mod tests {
         assert_eq!(result.unwrap(), format!("{}", "example_value".red()));
     }
 
+    #[test]
+    fn test_process_pattern_stripping_glob() {
+        // Test shortest and longest prefix
+        let value = "registry.example.com/team/app:1.0";
+        let result = process_pattern_stripping('#', value, Some("*/"), "#*/", false);
+        assert_eq!(result.unwrap(), "team/app:1.0");
+        let result = process_pattern_stripping('#', value, Some("#*/"), "##*/", false);
+        assert_eq!(result.unwrap(), "app:1.0");
+
+        // Test shortest and longest suffix
+        let value = "https://example.com/path?query=1?x";
+        let result = process_pattern_stripping('%', value, Some("\\?*"), "%\\?*", false);
+        assert_eq!(result.unwrap(), "https://example.com/path?query=1");
+        let result = process_pattern_stripping('%', value, Some("%\\?*"), "%%\\?*", false);
+        assert_eq!(result.unwrap(), "https://example.com/path");
+
+        // Test character classes and multi-byte characters
+        let value = "ÄÖÜ-1.2.3";
+        let result = process_pattern_stripping('#', value, Some("#*[-_]"), "##*[-_]", false);
+        assert_eq!(result.unwrap(), "1.2.3");
+        let result = process_pattern_stripping('%', value, Some(".?"), "%.?", false);
+        assert_eq!(result.unwrap(), "ÄÖÜ-1.2");
+
+        // Test no match
+        let result = process_pattern_stripping('%', value, Some("%x*"), "%%x*", true);
+        assert_eq!(result.unwrap(), "ÄÖÜ-1.2.3".red().to_string());
+    }
+
     #[test]
     fn test_process_pattern_stripping_invalid_operation() {
         // Test unreachable operation
@@ -712,6 +2769,71 @@ mod tests {
         assert!(result.is_err(), "Expected error from invalid operation");
     }
 
+    #[test]
+    fn test_process_pattern_replacement_glob() {
+        // Test first match, which is the longest match at the leftmost position
+        let result = process_pattern_replacement(
+            "db-01.prod.example.com",
+            Some("[0-9]*./"),
+            "VAR/[0-9]*./",
+            false,
+        );
+        assert_eq!(result, Ok("db-com".to_string()));
+
+        // Test all matches
+        let result =
+            process_pattern_replacement("a1b22c333", Some("/[0-9]/#"), "VAR//[0-9]/#", false);
+        assert_eq!(result, Ok("a#b##c###".to_string()));
+
+        // Test match at the start and the end
+        let result = process_pattern_replacement(
+            "https://example.com/path/",
+            Some("#*:\\/\\//"),
+            "VAR/#*:\\/\\//",
+            false,
+        );
+        assert_eq!(result, Ok("example.com/path/".to_string()));
+        let result =
+            process_pattern_replacement("app.tar.gz", Some("%.*/.zip"), "VAR/%.*/.zip", false);
+        assert_eq!(result, Ok("app.zip".to_string()));
+
+        // Test no match
+        let result = process_pattern_replacement("foobar", Some("#x*/y"), "VAR/#x*/y", true);
+        assert_eq!(result, Ok("foobar".red().to_string()));
+    }
+
+    #[test]
+    fn test_process_pattern_replacement_leading_slash() {
+        // Test all matches of a pattern starting with a slash, '${VAR////_}' and '${VAR///x/Y}'
+        let result = process_pattern_replacement("/usr/local/bin", Some("///_"), "VAR////_", false);
+        assert_eq!(result, Ok("_usr_local_bin".to_string()));
+        let result = process_pattern_replacement("a/x/b", Some("//x/Y"), "VAR///x/Y", false);
+        assert_eq!(result, Ok("aY/b".to_string()));
+
+        // Test first match of an escaped slash, '${VAR/\//_}' and '${VAR/\/x/Y}'
+        let result =
+            process_pattern_replacement("/usr/local/bin", Some("\\//_"), "VAR/\\//_", false);
+        assert_eq!(result, Ok("_usr/local/bin".to_string()));
+        let result = process_pattern_replacement("a/x/b/x", Some("\\/x/Y"), "VAR/\\/x/Y", false);
+        assert_eq!(result, Ok("aY/b/x".to_string()));
+    }
+
+    #[test]
+    fn test_process_pattern_replacement_matched_text() {
+        // Test '&' refers to the matched text
+        let result = process_pattern_replacement(
+            "host-a,host-b",
+            Some("/host-?/<&>"),
+            "VAR//host-?/<&>",
+            false,
+        );
+        assert_eq!(result, Ok("<host-a>,<host-b>".to_string()));
+
+        // Test escaped '&' and slashes in the replacement
+        let result = process_pattern_replacement("a", Some("a/\\&\\/&/"), "VAR/a/\\&\\/&/", false);
+        assert_eq!(result, Ok("&/a/".to_string()));
+    }
+
     #[test]
     fn test_process_pattern_replacement_empty_value() {
         // Test empty value
@@ -773,7 +2895,7 @@ mod tests {
         // Tests all matches
         let result = process_pattern_replacement(
             "Hello, world, world!",
-            Some("//world/moon"),
+            Some("/world/moon"),
             "Hello, world, world!//world/moon",
             false,
         );
@@ -782,7 +2904,7 @@ mod tests {
         // Tests no changed value
         let result = process_pattern_replacement(
             "Hello, world, world!",
-            Some("//world/world"),
+            Some("/world/world"),
             "Hello, world, world!//world/world",
             false,
         );
@@ -910,19 +3032,44 @@ mod tests {
     }
 
     #[test]
-    fn test_process_case_conversion_invalid_conversion() {
-        // Test ',' operation with invalid conversion
+    fn test_process_case_conversion_pattern() {
+        // A pattern that does not match the first character does not change the value
         let op = ',';
         let value = "HelloWorld";
         let operation_data = Some("invalid".to_string());
         let inner_expr = format!("{}{}", op, operation_data.as_deref().unwrap());
         let result =
             process_case_conversion(op, value, operation_data.as_deref(), &inner_expr, false);
-        assert!(result.is_err());
-        assert_eq!(
-            result.unwrap_err(),
-            format!("\"{inner_expr}\" - Invalid operation: ','")
-        );
+        assert_eq!(result.unwrap(), "HelloWorld");
+
+        let cases = [
+            ('^', "^[aeiou]", "hello world", "hEllO wOrld"),
+            ('^', "[a-h]", "hello", "Hello"),
+            ('^', "[i-z]", "hello", "hello"),
+            (',', ",[!L]", "HELLO", "heLLo"),
+            ('~', "~[a-z]", "Hello World", "HELLO WORLD"),
+        ];
+        for (op, data, value, expected) in cases {
+            let result = process_case_conversion(op, value, Some(data), data, false);
+            assert_eq!(result.unwrap(), expected, "{op}{data}");
+        }
+    }
+
+    #[test]
+    fn test_process_case_conversion_unicode() {
+        let cases = [
+            ('^', "", "élan", "Élan"),
+            (',', "", "Über", "über"),
+            ('^', "^", "straße", "STRASSE"),
+            (',', ",", "ÄÖÜ", "äöü"),
+            ('~', "", "émile", "Émile"),
+            ('~', "~", "Ça Va", "çA vA"),
+            ('^', "", "😀x", "😀x"),
+        ];
+        for (op, data, value, expected) in cases {
+            let result = process_case_conversion(op, value, Some(data), data, false);
+            assert_eq!(result.unwrap(), expected, "{op}{data} {value}");
+        }
     }
 
     #[test]
@@ -1037,6 +3184,45 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_process_substring_extraction_negative() {
+        let value = "HelloWorld";
+        let extract =
+            |data: &str| process_substring_extraction(value, Some(&data.to_string()), "VAR", false);
+
+        // Test negative offset
+        assert_eq!(extract(" -5").unwrap(), "World");
+        assert_eq!(extract("(-5):2").unwrap(), "Wo");
+        assert_eq!(extract(" -20").unwrap(), "");
+
+        // Test negative length
+        assert_eq!(extract("0:-1").unwrap(), "HelloWorl");
+        assert_eq!(extract(" -5:-2").unwrap(), "Wor");
+        assert_eq!(extract("5:-5").unwrap(), "");
+        assert!(extract("6:-5").is_err());
+
+        // Test empty length
+        assert_eq!(extract("2:").unwrap(), "");
+    }
+
+    #[test]
+    fn test_process_substring_extraction_out_of_range() {
+        let value = "Grüezi 👋";
+        let extract =
+            |data: &str| process_substring_extraction(value, Some(&data.to_string()), "VAR", false);
+
+        // Test offsets beyond the end never panic
+        assert_eq!(extract("20").unwrap(), "");
+        assert_eq!(extract("8").unwrap(), "");
+        assert_eq!(extract("2:100").unwrap(), "üezi 👋");
+        assert_eq!(extract("7:1").unwrap(), "👋");
+        assert_eq!(
+            extract("9223372036854775807:9223372036854775807").unwrap(),
+            ""
+        );
+        assert!(extract("0:-9223372036854775808").is_err());
+    }
+
     #[test]
     fn process_substring_extraction_empty_value() {
         // Test substring extraction with empty value
@@ -0,0 +1,304 @@
+use crate::dotenv;
+use crate::env_dir;
+use crate::flags::{Flag, Flags};
+use crate::settings::{Settings, VariableSource};
+use std::collections::HashMap;
+use std::env;
+
+/// `Variables` resolves variable names to their values during a single render.
+///
+/// Values assigned while rendering (e.g. by `${VAR:=default}`) are stored in a render-scoped
+/// table and take precedence over the environment, so that later references to the same
+/// variable in the same input resolve to the assigned value.
+///
+/// Variables loaded from env files and env directories take precedence over the environment,
+/// unless `prefer_environment` is set.
+#[derive(Debug, Default)]
+pub struct Variables {
+    /// Variables assigned during the current render.
+    assigned: HashMap<String, String>,
+    /// Variables loaded from env files and env directories.
+    loaded: HashMap<String, String>,
+    /// Whether the environment takes precedence over the loaded variables.
+    prefer_environment: bool,
+}
+
+impl Variables {
+    /// Creates a new instance of `Variables` with the given loaded variables.
+    ///
+    /// # Arguments
+    ///
+    /// * `loaded`: The variables loaded from env files and env directories.
+    /// * `prefer_environment`: Whether the environment takes precedence over `loaded`.
+    ///
+    pub fn new(loaded: HashMap<String, String>, prefer_environment: bool) -> Self {
+        return Variables {
+            assigned: HashMap::new(),
+            loaded,
+            prefer_environment,
+        };
+    }
+
+    /// Creates a new instance of `Variables` with the variables of the env files and env
+    /// directories of `settings`.
+    ///
+    /// The sources are loaded in the order they were specified, so a variable of a later source
+    /// overrides the same variable of an earlier one.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings`: The settings containing the sources.
+    /// * `flags`: Flags that affect the loaded variables, e.g. `--prefer-env`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result<Variables, String>` containing the variables, or an error message if a source
+    /// cannot be loaded.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if an env file or env directory cannot be read or is invalid.
+    ///
+    pub fn load(settings: &Settings, flags: &Flags) -> Result<Self, String> {
+        let mut loaded = HashMap::new();
+
+        for source in settings.variable_sources() {
+            let variables = match source {
+                VariableSource::EnvFile(path) => dotenv::load_file(path)?,
+                VariableSource::EnvDir(path) => env_dir::load_dir(path, flags)?,
+            };
+            loaded.extend(variables);
+        }
+
+        return Ok(Self::new(
+            loaded,
+            flags.is_flag_set(Flag::PreferEnvironment),
+        ));
+    }
+
+    /// Returns the value of the variable with the given `name`.
+    ///
+    /// Variables assigned during the render are looked up first, then the loaded variables and
+    /// the environment in the order of their precedence.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The name of the variable.
+    ///
+    /// # Returns
+    ///
+    /// An `Option<String>` containing the value of the variable, or `None` if it is not set.
+    ///
+    pub fn get(&self, name: &str) -> Option<String> {
+        if let Some(value) = self.assigned.get(name) {
+            return Some(value.clone());
+        }
+        let loaded = || self.loaded.get(name).cloned();
+        if self.prefer_environment {
+            return env::var(name).ok().or_else(loaded);
+        }
+        return loaded().or_else(|| env::var(name).ok());
+    }
+
+    /// Assigns `value` to the variable with the given `name` for the rest of the render.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The name of the variable.
+    /// * `value`: The value to assign.
+    ///
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.assigned.insert(name.to_string(), value.to_string());
+    }
+
+    /// Removes the value assigned to the variable with the given `name` during the render.
+    ///
+    /// The loaded variables and the environment are not changed, so the variable resolves to
+    /// its previous value again.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The name of the variable.
+    ///
+    /// # Returns
+    ///
+    /// An `Option<String>` containing the removed value, or `None` if no value was assigned.
+    ///
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        return self.assigned.remove(name);
+    }
+
+    /// Returns the names of all variables, sorted and without duplicates.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<String>` containing the names of the variables assigned during the render, the
+    /// loaded variables and the environment. Environment variables whose names are not valid
+    /// Unicode are skipped.
+    ///
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = env::vars_os()
+            .filter_map(|(name, _)| name.into_string().ok())
+            .chain(self.assigned.keys().cloned())
+            .chain(self.loaded.keys().cloned())
+            .collect();
+        names.sort();
+        names.dedup();
+        return names;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Setting;
+
+    #[test]
+    fn test_get_from_environment() {
+        env::set_var("VARIABLES_ENV_VAR", "value");
+        let variables = Variables::default();
+        assert_eq!(
+            variables.get("VARIABLES_ENV_VAR"),
+            Some("value".to_string())
+        );
+        assert_eq!(variables.get("VARIABLES_UNSET_VAR"), None);
+    }
+
+    #[test]
+    fn test_set_overrides_environment() {
+        env::set_var("VARIABLES_OVERRIDDEN_VAR", "value");
+        let mut variables = Variables::default();
+        variables.set("VARIABLES_OVERRIDDEN_VAR", "assigned");
+        variables.set("VARIABLES_ASSIGNED_VAR", "");
+        assert_eq!(
+            variables.get("VARIABLES_OVERRIDDEN_VAR"),
+            Some("assigned".to_string())
+        );
+        assert_eq!(variables.get("VARIABLES_ASSIGNED_VAR"), Some(String::new()));
+    }
+
+    #[test]
+    fn test_remove_restores_environment() {
+        env::set_var("VARIABLES_REMOVED_VAR", "env");
+        let mut variables = Variables::default();
+        variables.set("VARIABLES_REMOVED_VAR", "assigned");
+
+        assert_eq!(
+            variables.remove("VARIABLES_REMOVED_VAR"),
+            Some("assigned".to_string())
+        );
+        assert_eq!(variables.remove("VARIABLES_REMOVED_VAR"), None);
+        assert_eq!(
+            variables.get("VARIABLES_REMOVED_VAR"),
+            Some("env".to_string())
+        );
+    }
+
+    #[test]
+    fn test_loaded_precedence() {
+        env::set_var("VARIABLES_LOADED_ENV_VAR", "env");
+        let loaded = HashMap::from([
+            ("VARIABLES_LOADED_ENV_VAR".to_string(), "loaded".to_string()),
+            ("VARIABLES_LOADED_VAR".to_string(), "loaded".to_string()),
+        ]);
+
+        let mut variables = Variables::new(loaded.clone(), false);
+        assert_eq!(
+            variables.get("VARIABLES_LOADED_ENV_VAR"),
+            Some("loaded".to_string())
+        );
+        assert_eq!(
+            variables.get("VARIABLES_LOADED_VAR"),
+            Some("loaded".to_string())
+        );
+        variables.set("VARIABLES_LOADED_VAR", "assigned");
+        assert_eq!(
+            variables.get("VARIABLES_LOADED_VAR"),
+            Some("assigned".to_string())
+        );
+
+        let variables = Variables::new(loaded, true);
+        assert_eq!(
+            variables.get("VARIABLES_LOADED_ENV_VAR"),
+            Some("env".to_string())
+        );
+        assert_eq!(
+            variables.get("VARIABLES_LOADED_VAR"),
+            Some("loaded".to_string())
+        );
+        assert!(variables
+            .names()
+            .contains(&"VARIABLES_LOADED_VAR".to_string()));
+    }
+
+    #[test]
+    fn test_load_sources_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let env_file = dir.path().join(".env");
+        let env_dir = dir.path().join("secrets");
+        std::fs::write(
+            &env_file,
+            "VARIABLES_SOURCE_A=file\nVARIABLES_SOURCE_B=file\n",
+        )
+        .unwrap();
+        std::fs::create_dir(&env_dir).unwrap();
+        std::fs::write(env_dir.join("VARIABLES_SOURCE_B"), "dir\n").unwrap();
+
+        let mut settings = Settings::default();
+        for (setting, arg, path) in [
+            (Setting::EnvFile, "--env-file", &env_file),
+            (Setting::EnvDir, "--env-dir", &env_dir),
+        ] {
+            settings
+                .set(setting, arg, path.to_str(), &mut [].iter())
+                .unwrap();
+        }
+        let variables = Variables::load(&settings, &Flags::default()).unwrap();
+        assert_eq!(
+            variables.get("VARIABLES_SOURCE_A"),
+            Some("file".to_string())
+        );
+        assert_eq!(variables.get("VARIABLES_SOURCE_B"), Some("dir".to_string()));
+
+        settings
+            .set(
+                Setting::EnvFile,
+                "--env-file",
+                env_file.to_str(),
+                &mut [].iter(),
+            )
+            .unwrap();
+        let variables = Variables::load(&settings, &Flags::default()).unwrap();
+        assert_eq!(
+            variables.get("VARIABLES_SOURCE_B"),
+            Some("file".to_string())
+        );
+
+        settings
+            .set(
+                Setting::EnvDir,
+                "--env-dir",
+                Some("missing"),
+                &mut [].iter(),
+            )
+            .unwrap();
+        let result = Variables::load(&settings, &Flags::default());
+        assert!(result
+            .unwrap_err()
+            .starts_with("failed to read env directory 'missing': "));
+    }
+
+    #[test]
+    fn test_names() {
+        env::set_var("VARIABLES_NAMES_B", "value");
+        let mut variables = Variables::default();
+        variables.set("VARIABLES_NAMES_A", "assigned");
+        variables.set("VARIABLES_NAMES_B", "assigned");
+        let names: Vec<String> = variables
+            .names()
+            .into_iter()
+            .filter(|name| name.starts_with("VARIABLES_NAMES_"))
+            .collect();
+        assert_eq!(names, vec!["VARIABLES_NAMES_A", "VARIABLES_NAMES_B"]);
+    }
+}